@@ -0,0 +1,21 @@
+/// Implements the callback of [`SubAccountBuilder`](crate::factory::SubAccountBuilder) which
+/// refunds the deposit if the sub-account could not be created.
+#[macro_export]
+macro_rules! impl_sub_account_factory {
+    ($contract: ident) => {
+        use $crate::factory::SubAccountFactoryResolver;
+
+        #[near_bindgen]
+        impl SubAccountFactoryResolver for $contract {
+            #[private]
+            fn on_sub_account_created(
+                &mut self,
+                account_id: AccountId,
+                refund_to: AccountId,
+                amount: near_sdk::json_types::U128,
+            ) -> bool {
+                $crate::factory::resolve_sub_account_creation(account_id, refund_to, amount)
+            }
+        }
+    };
+}
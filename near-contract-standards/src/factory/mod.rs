@@ -0,0 +1,301 @@
+pub mod macros;
+
+pub use macros::*;
+
+use near_sdk::json_types::U128;
+use near_sdk::{
+    env, ext_contract, log, AccountId, Balance, Gas, Promise, PromiseResult, PublicKey,
+    StorageUsage,
+};
+
+/// Storage taken by the account record, `num_bytes_account` of the storage usage config in the
+/// runtime fees config of nearcore.
+pub const ACCOUNT_STORAGE_USAGE: StorageUsage = 100;
+/// Storage taken by an ED25519 full access key: 33 bytes of the Borsh-serialized public key, 9
+/// bytes of the access key with its nonce and permission, and the `num_extra_bytes_record` of 40
+/// bytes that nearcore charges for every record.
+pub const ACCESS_KEY_STORAGE_USAGE: StorageUsage = 82;
+
+const GAS_FOR_ON_SUB_ACCOUNT_CREATED: Gas = 5_000_000_000_000;
+const NO_DEPOSIT: Balance = 0;
+
+#[ext_contract(ext_self)]
+trait SubAccountFactoryResolver {
+    fn on_sub_account_created(
+        &mut self,
+        account_id: AccountId,
+        refund_to: AccountId,
+        amount: U128,
+    ) -> bool;
+}
+
+/// Callback of the factory scheduled by [`SubAccountBuilder::create`]. Implemented by
+/// [`impl_sub_account_factory`](crate::impl_sub_account_factory).
+pub trait SubAccountFactoryResolver {
+    /// Returns whether the account was created, refunding `amount` to `refund_to` otherwise.
+    fn on_sub_account_created(
+        &mut self,
+        account_id: AccountId,
+        refund_to: AccountId,
+        amount: U128,
+    ) -> bool;
+}
+
+/// Builder for a sub-account of the current account which is funded, optionally gets a full
+/// access key and a contract with an init call, all in a single batch of actions.
+///
+/// The deposit has to cover the storage of the account, the key and the code, see
+/// [`SubAccountBuilder::min_balance`]. If any of the actions fails, the deposit is refunded by the
+/// `on_sub_account_created` callback, which the factory implements with
+/// [`impl_sub_account_factory`](crate::impl_sub_account_factory).
+///
+/// ```ignore
+/// #[payable]
+/// pub fn create_token(&mut self, name: String, owner_id: AccountId) -> Promise {
+///     SubAccountBuilder::new(&name)
+///         .code_from_storage(b"code")
+///         .init_call("new", json!({ "owner_id": owner_id }).to_string().into_bytes(), GAS)
+///         .create()
+/// }
+/// ```
+pub struct SubAccountBuilder {
+    account_id: AccountId,
+    code: Option<Vec<u8>>,
+    public_key: Option<PublicKey>,
+    init_call: Option<(String, Vec<u8>, Gas)>,
+    deposit: Balance,
+    refund_to: AccountId,
+}
+
+impl SubAccountBuilder {
+    /// Starts building the account `<name>.<current account>`, funded with the attached deposit
+    /// which is refunded to the predecessor on failure.
+    pub fn new(name: &str) -> Self {
+        let account_id = env::current_account_id()
+            .get_sub_account(name)
+            .unwrap_or_else(|_| env::panic(b"Invalid sub-account name"));
+        Self {
+            account_id,
+            code: None,
+            public_key: None,
+            init_call: None,
+            deposit: env::attached_deposit(),
+            refund_to: env::predecessor_account_id(),
+        }
+    }
+
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// Deploys the given code to the new account.
+    pub fn code(mut self, code: Vec<u8>) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Deploys the code stored by the factory under `key`, e.g. uploaded once by the owner to
+    /// avoid passing it in every call.
+    pub fn code_from_storage(self, key: &[u8]) -> Self {
+        let code = env::storage_read(key).unwrap_or_else(|| env::panic(b"No code stored"));
+        self.code(code)
+    }
+
+    pub fn full_access_key(mut self, public_key: PublicKey) -> Self {
+        self.public_key = Some(public_key);
+        self
+    }
+
+    /// Calls `method_name` on the new account after deploying the code.
+    pub fn init_call(mut self, method_name: &str, args: Vec<u8>, gas: Gas) -> Self {
+        self.init_call = Some((method_name.to_string(), args, gas));
+        self
+    }
+
+    /// Overrides the amount transferred to the new account, which is the attached deposit by
+    /// default.
+    pub fn deposit(mut self, deposit: Balance) -> Self {
+        self.deposit = deposit;
+        self
+    }
+
+    /// Overrides the account that receives the deposit back if the creation fails.
+    pub fn refund_to(mut self, account_id: AccountId) -> Self {
+        self.refund_to = account_id;
+        self
+    }
+
+    /// Minimum balance of the new account to cover its storage before the init call. State
+    /// written by the init call has to be covered by an additional deposit.
+    pub fn min_balance(&self) -> Balance {
+        let code_len = self.code.as_ref().map_or(0, |code| code.len() as StorageUsage);
+        let keys = if self.public_key.is_some() { ACCESS_KEY_STORAGE_USAGE } else { 0 };
+        (ACCOUNT_STORAGE_USAGE + keys + code_len) as Balance * env::storage_byte_cost()
+    }
+
+    /// Schedules the creation of the account followed by the refund callback on the current
+    /// account. Panics if the deposit does not cover [`SubAccountBuilder::min_balance`].
+    pub fn create(self) -> Promise {
+        let min_balance = self.min_balance();
+        if self.deposit < min_balance {
+            env::panic(
+                format!(
+                    "The deposit {} is less than the {} required to create {}",
+                    self.deposit, min_balance, self.account_id
+                )
+                .as_bytes(),
+            );
+        }
+        let mut promise =
+            Promise::new(self.account_id.clone()).create_account().transfer(self.deposit);
+        if let Some(public_key) = self.public_key {
+            promise = promise.add_full_access_key(public_key);
+        }
+        if let Some(code) = self.code {
+            promise = promise.deploy_contract(code);
+        }
+        if let Some((method_name, args, gas)) = self.init_call {
+            promise = promise.function_call(method_name.into_bytes(), args, NO_DEPOSIT, gas);
+        }
+        promise.then(ext_self::on_sub_account_created(
+            self.account_id,
+            self.refund_to,
+            self.deposit.into(),
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_ON_SUB_ACCOUNT_CREATED,
+        ))
+    }
+}
+
+/// Implementation of [`SubAccountFactoryResolver::on_sub_account_created`].
+pub fn resolve_sub_account_creation(
+    account_id: AccountId,
+    refund_to: AccountId,
+    amount: U128,
+) -> bool {
+    assert_eq!(env::promise_results_count(), 1, "Expected one promise result");
+    match env::promise_result(0) {
+        PromiseResult::NotReady => unreachable!(),
+        PromiseResult::Successful(_) => true,
+        PromiseResult::Failed => {
+            log!("Failed to create {}, refunding {} to {}", account_id, amount.0, refund_to);
+            Promise::new(refund_to).transfer(amount.0);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::serde_json::{self, json};
+    use near_sdk::test_utils::{
+        accounts, ExecutedReceipt, MockedAction, ReceiptExecutor, VMContextBuilder,
+    };
+    use near_sdk::testing_env;
+
+    const CODE: &[u8] = b"code";
+    const GAS: Gas = 20_000_000_000_000;
+
+    fn factory() -> AccountId {
+        "factory.near".parse().unwrap()
+    }
+
+    fn token() -> AccountId {
+        "token.factory.near".parse().unwrap()
+    }
+
+    fn set_context(attached_deposit: Balance) {
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(factory())
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(attached_deposit)
+            .prepaid_gas(100_000_000_000_000)
+            .build());
+    }
+
+    fn min_balance() -> Balance {
+        (ACCOUNT_STORAGE_USAGE + CODE.len() as StorageUsage) as Balance * env::storage_byte_cost()
+    }
+
+    /// Executes the created receipts with the factory callback and the given init call.
+    fn execute(init: fn()) -> Vec<ExecutedReceipt> {
+        let mut executor = ReceiptExecutor::new();
+        executor.register(token(), move |call| {
+            assert_eq!(call.method_name, "new");
+            init();
+        });
+        executor.register(factory(), |call| {
+            assert_eq!(call.method_name, "on_sub_account_created");
+            let args: serde_json::Value = serde_json::from_slice(&call.args).unwrap();
+            let created = resolve_sub_account_creation(
+                serde_json::from_value(args["account_id"].clone()).unwrap(),
+                serde_json::from_value(args["refund_to"].clone()).unwrap(),
+                serde_json::from_value(args["amount"].clone()).unwrap(),
+            );
+            env::value_return(&serde_json::to_vec(&created).unwrap());
+        });
+        executor.execute_created_receipts()
+    }
+
+    fn create() {
+        SubAccountBuilder::new("token")
+            .code(CODE.to_vec())
+            .init_call("new", json!({ "owner_id": accounts(0) }).to_string().into_bytes(), GAS)
+            .create();
+    }
+
+    #[test]
+    fn test_create() {
+        set_context(min_balance());
+        create();
+
+        let executed = execute(|| {});
+        assert_eq!(executed.len(), 2);
+        let actions = &executed[0].receipt.actions;
+        assert_eq!(executed[0].receipt.receiver_id, token());
+        assert!(matches!(actions[0], MockedAction::CreateAccount));
+        assert!(
+            matches!(actions[1], MockedAction::Transfer { deposit } if deposit == min_balance())
+        );
+        assert!(matches!(&actions[2], MockedAction::DeployContract { code } if code == CODE));
+        assert_eq!(executed[1].result, PromiseResult::Successful(b"true".to_vec()));
+    }
+
+    #[test]
+    fn test_create_failed_refund() {
+        let deposit = 2 * min_balance();
+        set_context(deposit);
+        create();
+
+        let executed = execute(|| env::panic(b"Init failed"));
+        assert_eq!(executed[0].result, PromiseResult::Failed);
+        assert_eq!(executed[1].result, PromiseResult::Successful(b"false".to_vec()));
+        assert_eq!(
+            executed[1].logs,
+            vec![format!("Failed to create {}, refunding {} to {}", token(), deposit, accounts(0))]
+        );
+        let refund = &executed[2].receipt;
+        assert_eq!(refund.receiver_id, accounts(0));
+        assert!(matches!(refund.actions[0], MockedAction::Transfer { deposit: d } if d == deposit));
+    }
+
+    #[test]
+    #[should_panic(expected = "is less than the")]
+    fn test_create_not_enough_deposit() {
+        set_context(min_balance() - 1);
+        create();
+    }
+
+    #[test]
+    fn test_min_balance_with_key() {
+        set_context(0);
+        let builder =
+            SubAccountBuilder::new("token").full_access_key(vec![0; 33]).code(CODE.to_vec());
+        assert_eq!(
+            builder.min_balance(),
+            min_balance() + ACCESS_KEY_STORAGE_USAGE as Balance * env::storage_byte_cost()
+        );
+    }
+}
@@ -1,3 +1,5 @@
+/// Factory helpers to create sub-accounts with a deployed contract.
+pub mod factory;
 /// Fungible tokens as described in [by the spec](https://nomicon.io/Standards/FungibleToken/README.html).
 pub mod fungible_token;
 /// Non-fungible tokens as described in [by the spec](https://nomicon.io/Standards/NonFungibleToken/README.html).
@@ -1,9 +1,19 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::json_types::U64;
-use near_sdk::{env, AccountId, Duration, Promise, Timestamp};
+use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::{env, AccountId, CryptoHash, Duration, Promise, Timestamp};
+
+mod multisig;
+pub use multisig::{ApproversChange, GovernedUpgradable, MultisigUpgrade};
 
 type WrappedDuration = U64;
 
+/// Key of the staged code.
+const UPGRADE_KEY: &[u8] = b"upgrade";
+/// Prefix of the keys of code chunks, followed by the little-endian `u32` index of the chunk.
+const UPGRADE_CHUNK_PREFIX: &[u8] = b"upgrade-chunk";
+/// Key of the little-endian `u32` upper bound of the indices of the staged chunks.
+const UPGRADE_CHUNK_COUNT_KEY: &[u8] = b"upgrade-chunk-count";
+
 pub trait Ownable {
     fn assert_owner(&self) {
         assert_eq!(env::predecessor_account_id(), self.get_owner());
@@ -25,6 +35,30 @@ pub trait Upgradable {
     }
 }
 
+/// Staging of code that is too large to be passed in a single transaction.
+pub trait ChunkedUpgradable: Upgradable {
+    /// Stores a chunk of the code. Chunks are numbered from `0` and can be uploaded in any order
+    /// and overwritten until the staging is finalized.
+    fn stage_chunk(&mut self, index: u32, chunk: Vec<u8>);
+
+    /// Joins the chunks `0..num_chunks` and stages them like [`Upgradable::stage_code`], if their
+    /// SHA-256 hash matches `expected_sha256`. Panics if one of these chunks is missing. All staged
+    /// chunks are then removed from the storage, including chunks with a higher index, e.g. left
+    /// over from an earlier upload.
+    fn finalize_staging(
+        &mut self,
+        num_chunks: u32,
+        expected_sha256: Base58CryptoHash,
+        timestamp: Timestamp,
+    );
+
+    /// Removes the staged code from the storage once the staging period is over, e.g. to deploy
+    /// it to a new sub-account with [`SubAccountBuilder::code`] or to the current account.
+    ///
+    /// [`SubAccountBuilder::code`]: crate::factory::SubAccountBuilder::code
+    fn take_staged_code(&mut self) -> Vec<u8>;
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct Upgrade {
     pub owner: AccountId,
@@ -61,7 +95,7 @@ impl Upgradable for Upgrade {
             "Timestamp must be later than staging duration"
         );
         // Writes directly into storage to avoid serialization penalty by using default struct.
-        env::storage_write(b"upgrade", &code);
+        env::storage_write(UPGRADE_KEY, &code);
         self.staging_timestamp = timestamp;
     }
 
@@ -75,8 +109,163 @@ impl Upgradable for Upgrade {
                 .into_bytes(),
             );
         }
-        let code = env::storage_read(b"upgrade").expect("No upgrade code available");
-        env::storage_remove(b"upgrade");
+        let code = env::storage_read(UPGRADE_KEY).expect("No upgrade code available");
+        env::storage_remove(UPGRADE_KEY);
         Promise::new(env::current_account_id()).deploy_contract(code)
     }
 }
+
+fn chunk_key(index: u32) -> Vec<u8> {
+    [UPGRADE_CHUNK_PREFIX, &index.to_le_bytes()].concat()
+}
+
+fn staged_chunk_count() -> u32 {
+    env::storage_read(UPGRADE_CHUNK_COUNT_KEY).map_or(0, |count| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&count);
+        u32::from_le_bytes(bytes)
+    })
+}
+
+impl ChunkedUpgradable for Upgrade {
+    fn stage_chunk(&mut self, index: u32, chunk: Vec<u8>) {
+        self.assert_owner();
+        env::storage_write(&chunk_key(index), &chunk);
+        if index >= staged_chunk_count() {
+            env::storage_write(UPGRADE_CHUNK_COUNT_KEY, &(index + 1).to_le_bytes());
+        }
+    }
+
+    fn finalize_staging(
+        &mut self,
+        num_chunks: u32,
+        expected_sha256: Base58CryptoHash,
+        timestamp: Timestamp,
+    ) {
+        self.assert_owner();
+        let mut code = vec![];
+        for index in 0..num_chunks {
+            let chunk = env::storage_read(&chunk_key(index))
+                .unwrap_or_else(|| env::panic(format!("Chunk {} is not staged", index).as_bytes()));
+            code.extend_from_slice(&chunk);
+        }
+        let expected_sha256: CryptoHash = expected_sha256.into();
+        if env::sha256(&code) != expected_sha256 {
+            env::panic(b"Hash of the staged chunks does not match the expected hash");
+        }
+        for index in 0..num_chunks.max(staged_chunk_count()) {
+            env::storage_remove(&chunk_key(index));
+        }
+        env::storage_remove(UPGRADE_CHUNK_COUNT_KEY);
+        self.stage_code(code, timestamp);
+    }
+
+    fn take_staged_code(&mut self) -> Vec<u8> {
+        if env::block_timestamp() < self.staging_timestamp {
+            env::panic(
+                format!("Deploy code too early: staging ends on {}", self.staging_timestamp)
+                    .as_bytes(),
+            );
+        }
+        let code = env::storage_read(UPGRADE_KEY).expect("No upgrade code available");
+        env::storage_remove(UPGRADE_KEY);
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const STAGING_DURATION: Duration = 100;
+    const DEPLOY_TIMESTAMP: Timestamp = 200;
+
+    fn code_hash(code: &[u8]) -> Base58CryptoHash {
+        let mut hash = CryptoHash::default();
+        hash.copy_from_slice(&env::sha256(code));
+        hash.into()
+    }
+
+    fn set_context(block_timestamp: Timestamp) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    fn upgrade() -> Upgrade {
+        Upgrade::new(accounts(0), STAGING_DURATION)
+    }
+
+    #[test]
+    fn test_chunks_out_of_order() {
+        let mut upgrade = upgrade();
+        set_context(0);
+        upgrade.stage_chunk(2, b"de".to_vec());
+        upgrade.stage_chunk(0, b"co".to_vec());
+        upgrade.stage_chunk(1, b"de ".to_vec());
+        upgrade.stage_chunk(1, b"de-".to_vec());
+        upgrade.finalize_staging(3, code_hash(b"code-de"), DEPLOY_TIMESTAMP);
+        assert_eq!(env::storage_read(UPGRADE_KEY), Some(b"code-de".to_vec()));
+        assert!((0..3).all(|index| !env::storage_has_key(&chunk_key(index))));
+    }
+
+    #[test]
+    #[should_panic(expected = "Hash of the staged chunks does not match the expected hash")]
+    fn test_chunks_hash_mismatch() {
+        let mut upgrade = upgrade();
+        set_context(0);
+        upgrade.stage_chunk(0, b"co".to_vec());
+        upgrade.stage_chunk(1, b"de".to_vec());
+        upgrade.finalize_staging(2, code_hash(b"other code"), DEPLOY_TIMESTAMP);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk 1 is not staged")]
+    fn test_chunks_missing() {
+        let mut upgrade = upgrade();
+        set_context(0);
+        upgrade.stage_chunk(0, b"co".to_vec());
+        upgrade.stage_chunk(2, b"de".to_vec());
+        upgrade.finalize_staging(3, code_hash(b"code"), DEPLOY_TIMESTAMP);
+    }
+
+    #[test]
+    fn test_leftover_chunks() {
+        let mut upgrade = upgrade();
+        set_context(0);
+        // Chunk 2 is left over from an earlier, longer upload.
+        upgrade.stage_chunk(0, b"old ".to_vec());
+        upgrade.stage_chunk(1, b"long ".to_vec());
+        upgrade.stage_chunk(2, b"code".to_vec());
+        upgrade.stage_chunk(0, b"co".to_vec());
+        upgrade.stage_chunk(1, b"de".to_vec());
+        upgrade.finalize_staging(2, code_hash(b"code"), DEPLOY_TIMESTAMP);
+        assert_eq!(env::storage_read(UPGRADE_KEY), Some(b"code".to_vec()));
+        assert!((0..3).all(|index| !env::storage_has_key(&chunk_key(index))));
+        assert!(!env::storage_has_key(UPGRADE_CHUNK_COUNT_KEY));
+    }
+
+    #[test]
+    #[should_panic(expected = "Deploy code too early: staging ends on 200")]
+    fn test_take_staged_code_too_early() {
+        let mut upgrade = upgrade();
+        set_context(0);
+        upgrade.stage_code(b"code".to_vec(), DEPLOY_TIMESTAMP);
+        set_context(DEPLOY_TIMESTAMP - 1);
+        upgrade.take_staged_code();
+    }
+
+    #[test]
+    fn test_deploy_after_staging() {
+        let mut upgrade = upgrade();
+        set_context(0);
+        upgrade.stage_chunk(0, b"code".to_vec());
+        upgrade.finalize_staging(1, code_hash(b"code"), DEPLOY_TIMESTAMP);
+        set_context(DEPLOY_TIMESTAMP);
+        assert_eq!(upgrade.take_staged_code(), b"code".to_vec());
+        assert!(!env::storage_has_key(UPGRADE_KEY));
+    }
+}
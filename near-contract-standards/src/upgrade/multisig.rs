@@ -0,0 +1,564 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::serde_json::{json, Value};
+use near_sdk::{env, log, AccountId, CryptoHash, Duration, Promise, Timestamp};
+
+/// Key of the staged code, separate from the key used by [`Upgrade`](super::Upgrade).
+const MULTISIG_UPGRADE_KEY: &[u8] = b"multisig-upgrade";
+
+/// Logs an event in the NEP-297 format: `EVENT_JSON:` followed by a JSON object with the
+/// `multisig_upgrade` standard, its version, the name of the event and its data.
+fn log_event(event: &str, data: Value) {
+    let event = json!({
+        "standard": "multisig_upgrade",
+        "version": "1.0.0",
+        "event": event,
+        "data": data,
+    });
+    log!("EVENT_JSON:{}", event);
+}
+
+/// Upgrade flow where `threshold` of the `approvers` have to approve the hash of the code, after
+/// which the code can be deployed once the staging duration has passed.
+pub trait GovernedUpgradable {
+    fn get_approvers(&self) -> Vec<AccountId>;
+    fn get_threshold(&self) -> u32;
+
+    /// Proposes the code with the given hash. Counts as an approval of the proposer. Panics if
+    /// another proposal is pending, which has to be cancelled first.
+    fn propose_upgrade(&mut self, code_hash: Base58CryptoHash);
+
+    /// Approves the current proposal. Takes the hash to make sure the approver approves the code
+    /// they have reviewed. The staging duration starts once the threshold is reached.
+    fn approve_upgrade(&mut self, code_hash: Base58CryptoHash);
+
+    /// Votes to withdraw the current proposal and its approvals. The proposal is withdrawn once
+    /// `threshold` approvers voted for it, or right away if the proposer calls it before the
+    /// proposal reached the threshold.
+    fn cancel_upgrade(&mut self);
+
+    /// Votes to replace the approvers and the threshold. The change is applied once `threshold`
+    /// of the current approvers voted for the same approvers and threshold, and withdraws the
+    /// current proposal, which was approved by the previous approvers. Voting again replaces the
+    /// previous vote of the approver.
+    fn change_approvers(&mut self, approvers: Vec<AccountId>, threshold: u32);
+
+    /// Stores the code of the current proposal. Can be called by anyone, since the code must
+    /// match the proposed hash.
+    fn stage_code(&mut self, code: Vec<u8>);
+
+    /// Deploys the staged code to the current account once the proposal is approved and the
+    /// staging duration has passed.
+    fn deploy_code(&mut self) -> Promise;
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct MultisigUpgrade {
+    pub approvers: Vec<AccountId>,
+    pub threshold: u32,
+    pub staging_duration: Duration,
+    pub code_hash: Option<CryptoHash>,
+    pub approvals: Vec<AccountId>,
+    /// Time when the proposal reached the threshold.
+    pub approved_timestamp: Option<Timestamp>,
+    /// Approvers who voted to withdraw the current proposal.
+    pub cancellations: Vec<AccountId>,
+    /// Votes of the approvers for new approvers and a new threshold.
+    pub approvers_votes: Vec<(AccountId, ApproversChange)>,
+}
+
+/// Approvers and threshold an approver voted for, see [`GovernedUpgradable::change_approvers`].
+#[derive(BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub struct ApproversChange {
+    pub approvers: Vec<AccountId>,
+    pub threshold: u32,
+}
+
+fn assert_valid_approvers(approvers: &[AccountId], threshold: u32) {
+    assert!(
+        threshold > 0 && threshold as usize <= approvers.len(),
+        "Threshold must be between 1 and the number of approvers"
+    );
+    for (i, approver) in approvers.iter().enumerate() {
+        assert!(!approvers[..i].contains(approver), "Duplicate approver {}", approver);
+    }
+}
+
+impl MultisigUpgrade {
+    pub fn new(approvers: Vec<AccountId>, threshold: u32, staging_duration: Duration) -> Self {
+        assert_valid_approvers(&approvers, threshold);
+        Self {
+            approvers,
+            threshold,
+            staging_duration,
+            code_hash: None,
+            approvals: vec![],
+            approved_timestamp: None,
+            cancellations: vec![],
+            approvers_votes: vec![],
+        }
+    }
+
+    fn assert_approver(&self) -> AccountId {
+        let account_id = env::predecessor_account_id();
+        if !self.approvers.contains(&account_id) {
+            env::panic(b"Only approvers can govern upgrades");
+        }
+        account_id
+    }
+
+    fn assert_proposed(&self, code_hash: &CryptoHash) {
+        if self.code_hash.as_ref() != Some(code_hash) {
+            env::panic(b"Code hash does not match the current proposal");
+        }
+    }
+
+    /// Hash of the current proposal in the format of the events.
+    fn proposed_code_hash(&self) -> Base58CryptoHash {
+        self.code_hash.unwrap_or_else(|| env::panic(b"No upgrade proposed")).into()
+    }
+
+    fn add_approval(&mut self, approver: AccountId) {
+        self.approvals.push(approver.clone());
+        let mut data = json!({
+            "code_hash": self.proposed_code_hash(),
+            "account_id": approver,
+            "approvals": self.approvals.len(),
+        });
+        if self.approved_timestamp.is_none() && self.approvals.len() >= self.threshold as usize {
+            let timestamp = env::block_timestamp();
+            self.approved_timestamp = Some(timestamp);
+            data["deploy_after"] = json!(U64::from(timestamp + self.staging_duration));
+        }
+        log_event("upgrade_approved", data);
+    }
+
+    fn clear(&mut self) {
+        self.code_hash = None;
+        self.approvals.clear();
+        self.approved_timestamp = None;
+        self.cancellations.clear();
+        env::storage_remove(MULTISIG_UPGRADE_KEY);
+    }
+
+    /// Withdraws the current proposal, if any.
+    fn cancel(&mut self) {
+        if let Some(code_hash) = self.code_hash {
+            self.clear();
+            log_event(
+                "upgrade_cancelled",
+                json!({ "code_hash": Base58CryptoHash::from(code_hash) }),
+            );
+        }
+    }
+}
+
+impl GovernedUpgradable for MultisigUpgrade {
+    fn get_approvers(&self) -> Vec<AccountId> {
+        self.approvers.clone()
+    }
+
+    fn get_threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    fn propose_upgrade(&mut self, code_hash: Base58CryptoHash) {
+        let proposer = self.assert_approver();
+        if self.code_hash.is_some() {
+            env::panic(b"Another upgrade is already proposed, cancel it first");
+        }
+        log_event("upgrade_proposed", json!({ "code_hash": code_hash, "account_id": proposer }));
+        self.code_hash = Some(code_hash.into());
+        self.add_approval(proposer);
+    }
+
+    fn approve_upgrade(&mut self, code_hash: Base58CryptoHash) {
+        let approver = self.assert_approver();
+        self.assert_proposed(&code_hash.into());
+        if self.approvals.contains(&approver) {
+            env::panic(b"Upgrade is already approved by this account");
+        }
+        self.add_approval(approver);
+    }
+
+    fn cancel_upgrade(&mut self) {
+        let account_id = self.assert_approver();
+        let code_hash = self.proposed_code_hash();
+        // The proposer is the first approval.
+        if self.approved_timestamp.is_none() && self.approvals.first() == Some(&account_id) {
+            self.cancel();
+            return;
+        }
+        if self.cancellations.contains(&account_id) {
+            env::panic(b"Cancellation is already requested by this account");
+        }
+        self.cancellations.push(account_id.clone());
+        log_event(
+            "upgrade_cancel_requested",
+            json!({
+                "code_hash": code_hash,
+                "account_id": account_id,
+                "cancellations": self.cancellations.len(),
+            }),
+        );
+        if self.cancellations.len() >= self.threshold as usize {
+            self.cancel();
+        }
+    }
+
+    fn change_approvers(&mut self, approvers: Vec<AccountId>, threshold: u32) {
+        let account_id = self.assert_approver();
+        assert_valid_approvers(&approvers, threshold);
+        let change = ApproversChange { approvers, threshold };
+        self.approvers_votes.retain(|(voter, _)| voter != &account_id);
+        self.approvers_votes.push((account_id.clone(), change.clone()));
+        let votes = self.approvers_votes.iter().filter(|(_, vote)| vote == &change).count();
+        log_event(
+            "approvers_change_requested",
+            json!({
+                "approvers": change.approvers,
+                "threshold": change.threshold,
+                "account_id": account_id,
+                "votes": votes,
+            }),
+        );
+        if votes >= self.threshold as usize {
+            self.cancel();
+            self.approvers_votes.clear();
+            log_event(
+                "approvers_changed",
+                json!({ "approvers": change.approvers, "threshold": change.threshold }),
+            );
+            self.approvers = change.approvers;
+            self.threshold = change.threshold;
+        }
+    }
+
+    fn stage_code(&mut self, code: Vec<u8>) {
+        let mut code_hash = CryptoHash::default();
+        code_hash.copy_from_slice(&env::sha256(&code));
+        self.assert_proposed(&code_hash);
+        env::storage_write(MULTISIG_UPGRADE_KEY, &code);
+        log_event("upgrade_staged", json!({ "code_hash": Base58CryptoHash::from(code_hash) }));
+    }
+
+    fn deploy_code(&mut self) -> Promise {
+        let approved_timestamp =
+            self.approved_timestamp.unwrap_or_else(|| env::panic(b"Upgrade is not approved"));
+        let deploy_timestamp = approved_timestamp + self.staging_duration;
+        if env::block_timestamp() < deploy_timestamp {
+            env::panic(
+                format!("Deploy code too early: staging ends on {}", deploy_timestamp).as_bytes(),
+            );
+        }
+        let code = env::storage_read(MULTISIG_UPGRADE_KEY).expect("No upgrade code available");
+        let code_hash = self.proposed_code_hash();
+        self.clear();
+        log_event("upgrade_deployed", json!({ "code_hash": code_hash }));
+        Promise::new(env::current_account_id()).deploy_contract(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    const CODE: &[u8] = b"code";
+    const STAGING_DURATION: Duration = 100;
+
+    fn code_hash(code: &[u8]) -> Base58CryptoHash {
+        let mut hash = CryptoHash::default();
+        hash.copy_from_slice(&env::sha256(code));
+        hash.into()
+    }
+
+    fn set_context(predecessor: usize, block_timestamp: Timestamp) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(predecessor))
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    fn upgrade() -> MultisigUpgrade {
+        MultisigUpgrade::new(vec![accounts(0), accounts(1), accounts(2)], 2, STAGING_DURATION)
+    }
+
+    fn event(event: &str, data: Value) -> String {
+        format!(
+            "EVENT_JSON:{}",
+            json!({
+                "standard": "multisig_upgrade",
+                "version": "1.0.0",
+                "event": event,
+                "data": data,
+            })
+        )
+    }
+
+    #[test]
+    fn test_approve_and_deploy() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.stage_code(CODE.to_vec());
+        assert!(upgrade.approved_timestamp.is_none());
+        assert_eq!(
+            get_logs(),
+            vec![
+                event(
+                    "upgrade_proposed",
+                    json!({ "code_hash": code_hash(CODE), "account_id": "alice" })
+                ),
+                event(
+                    "upgrade_approved",
+                    json!({ "code_hash": code_hash(CODE), "account_id": "alice", "approvals": 1 })
+                ),
+                event("upgrade_staged", json!({ "code_hash": code_hash(CODE) })),
+            ]
+        );
+        assert!(env::storage_has_key(MULTISIG_UPGRADE_KEY));
+        assert!(!env::storage_has_key(crate::upgrade::UPGRADE_KEY));
+
+        set_context(1, 10);
+        upgrade.approve_upgrade(code_hash(CODE));
+        assert_eq!(upgrade.approved_timestamp, Some(10));
+        assert_eq!(
+            get_logs(),
+            vec![event(
+                "upgrade_approved",
+                json!({
+                    "code_hash": code_hash(CODE),
+                    "account_id": "bob",
+                    "approvals": 2,
+                    "deploy_after": "110",
+                })
+            )]
+        );
+
+        set_context(5, 10 + STAGING_DURATION);
+        upgrade.deploy_code();
+        assert!(upgrade.code_hash.is_none());
+        assert!(upgrade.approvals.is_empty());
+        assert!(!env::storage_has_key(MULTISIG_UPGRADE_KEY));
+        assert_eq!(
+            get_logs(),
+            vec![event("upgrade_deployed", json!({ "code_hash": code_hash(CODE) }))]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Deploy code too early: staging ends on 110")]
+    fn test_deploy_too_early() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.stage_code(CODE.to_vec());
+        set_context(1, 10);
+        upgrade.approve_upgrade(code_hash(CODE));
+        set_context(1, 10 + STAGING_DURATION - 1);
+        upgrade.deploy_code();
+    }
+
+    #[test]
+    #[should_panic(expected = "Upgrade is not approved")]
+    fn test_deploy_below_threshold() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.stage_code(CODE.to_vec());
+        set_context(0, 10 * STAGING_DURATION);
+        upgrade.deploy_code();
+    }
+
+    #[test]
+    #[should_panic(expected = "Upgrade is already approved by this account")]
+    fn test_double_approval() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.approve_upgrade(code_hash(CODE));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only approvers can govern upgrades")]
+    fn test_not_approver() {
+        let mut upgrade = upgrade();
+        set_context(3, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+    }
+
+    #[test]
+    #[should_panic(expected = "Another upgrade is already proposed, cancel it first")]
+    fn test_propose_while_pending() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        set_context(1, 0);
+        upgrade.propose_upgrade(code_hash(b"other code"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Code hash does not match the current proposal")]
+    fn test_approve_stale_proposal() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.cancel_upgrade();
+        set_context(1, 0);
+        upgrade.propose_upgrade(code_hash(b"other code"));
+        set_context(2, 0);
+        upgrade.approve_upgrade(code_hash(CODE));
+    }
+
+    #[test]
+    #[should_panic(expected = "Code hash does not match the current proposal")]
+    fn test_stage_wrong_code() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.stage_code(b"other code".to_vec());
+    }
+
+    #[test]
+    fn test_cancel_by_proposer() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.stage_code(CODE.to_vec());
+        upgrade.cancel_upgrade();
+        assert!(upgrade.code_hash.is_none());
+        assert!(upgrade.approvals.is_empty());
+        assert!(!env::storage_has_key(MULTISIG_UPGRADE_KEY));
+        assert_eq!(
+            get_logs().last(),
+            Some(&event("upgrade_cancelled", json!({ "code_hash": code_hash(CODE) })))
+        );
+    }
+
+    #[test]
+    fn test_cancel_by_threshold() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.stage_code(CODE.to_vec());
+        set_context(1, 0);
+        upgrade.approve_upgrade(code_hash(CODE));
+
+        // A single approver can't veto the approved proposal, not even the proposer.
+        set_context(0, 0);
+        upgrade.cancel_upgrade();
+        assert!(upgrade.code_hash.is_some());
+        assert_eq!(
+            get_logs(),
+            vec![event(
+                "upgrade_cancel_requested",
+                json!({ "code_hash": code_hash(CODE), "account_id": "alice", "cancellations": 1 })
+            )]
+        );
+
+        set_context(2, 0);
+        upgrade.cancel_upgrade();
+        assert!(upgrade.code_hash.is_none());
+        assert!(upgrade.approvals.is_empty());
+        assert!(upgrade.approved_timestamp.is_none());
+        assert!(upgrade.cancellations.is_empty());
+        assert!(!env::storage_has_key(MULTISIG_UPGRADE_KEY));
+        assert_eq!(
+            get_logs().last(),
+            Some(&event("upgrade_cancelled", json!({ "code_hash": code_hash(CODE) })))
+        );
+
+        // A new proposal starts without the approvals of the cancelled one.
+        upgrade.propose_upgrade(code_hash(CODE));
+        assert_eq!(upgrade.approvals, vec![accounts(2)]);
+        assert!(upgrade.approved_timestamp.is_none());
+    }
+
+    #[test]
+    fn test_cancel_by_single_approver() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        set_context(1, 0);
+        upgrade.cancel_upgrade();
+        assert!(upgrade.code_hash.is_some());
+        assert_eq!(upgrade.cancellations, vec![accounts(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cancellation is already requested by this account")]
+    fn test_double_cancellation() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        set_context(1, 0);
+        upgrade.cancel_upgrade();
+        upgrade.cancel_upgrade();
+    }
+
+    #[test]
+    fn test_change_approvers() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        upgrade.change_approvers(vec![accounts(1), accounts(3)], 1);
+        set_context(1, 0);
+        // A vote for a different change doesn't count towards the first one.
+        upgrade.change_approvers(vec![accounts(3)], 1);
+        assert_eq!(upgrade.approvers.len(), 3);
+        // Voting again replaces the previous vote.
+        upgrade.change_approvers(vec![accounts(1), accounts(3)], 1);
+        assert_eq!(upgrade.approvers, vec![accounts(1), accounts(3)]);
+        assert_eq!(upgrade.threshold, 1);
+        assert!(upgrade.approvers_votes.is_empty());
+        // The proposal approved by the previous approvers is withdrawn.
+        assert!(upgrade.code_hash.is_none());
+        let logs = get_logs();
+        assert_eq!(
+            logs[logs.len() - 2..],
+            [
+                event("upgrade_cancelled", json!({ "code_hash": code_hash(CODE) })),
+                event(
+                    "approvers_changed",
+                    json!({ "approvers": ["bob", "danny"], "threshold": 1 })
+                ),
+            ]
+        );
+
+        set_context(3, 0);
+        upgrade.propose_upgrade(code_hash(CODE));
+        assert_eq!(upgrade.approved_timestamp, Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Threshold must be between 1 and the number of approvers")]
+    fn test_change_approvers_invalid_threshold() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.change_approvers(vec![accounts(1)], 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only approvers can govern upgrades")]
+    fn test_change_approvers_not_approver() {
+        let mut upgrade = upgrade();
+        set_context(3, 0);
+        upgrade.change_approvers(vec![accounts(3)], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "No upgrade proposed")]
+    fn test_cancel_without_proposal() {
+        let mut upgrade = upgrade();
+        set_context(0, 0);
+        upgrade.cancel_upgrade();
+    }
+
+    #[test]
+    #[should_panic(expected = "Threshold must be between 1 and the number of approvers")]
+    fn test_threshold_too_high() {
+        MultisigUpgrade::new(vec![accounts(0)], 2, STAGING_DURATION);
+    }
+}
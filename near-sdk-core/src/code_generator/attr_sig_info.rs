@@ -160,25 +160,20 @@ impl AttrSigInfo {
         result
     }
 
-    /// Create code that deserializes arguments that were decorated with `#[callback]`
+    /// Create code that deserializes arguments that were decorated with `#[callback]` or
+    /// `#[callback_result]`.
     pub fn callback_deserialization(&self) -> TokenStream2 {
         self
             .args
             .iter()
             .filter(|arg| match arg.bindgen_ty {
-                BindgenArgType::CallbackArg => true,
+                BindgenArgType::CallbackArg | BindgenArgType::CallbackResultArg => true,
                 _ => false,
             })
             .enumerate()
             .fold(TokenStream2::new(), |acc, (idx, arg)| {
                 let idx = idx as u64;
                 let ArgInfo { mutability, ident, ty, .. } = arg;
-                let read_data = quote! {
-                let data: Vec<u8> = match near_sdk::env::promise_result(#idx) {
-                    near_sdk::PromiseResult::Successful(x) => x,
-                    _ => panic!("Callback computation {} was not successful", #idx)
-                };
-            };
                 let invocation = match arg.serializer_ty {
                     SerializerType::JSON => quote! {
                         near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON")
@@ -187,11 +182,35 @@ impl AttrSigInfo {
                         near_sdk::borsh::BorshDeserialize::try_from_slice(&data).expect("Failed to deserialize callback using Borsh")
                     },
                 };
+                let deserialization = match arg.bindgen_ty {
+                    // A result which can't be deserialized is handed to the method as an error.
+                    BindgenArgType::CallbackResultArg => {
+                        let invocation = match arg.serializer_ty {
+                            SerializerType::JSON => quote! {
+                                near_sdk::serde_json::from_slice(&data)
+                            },
+                            SerializerType::Borsh => quote! {
+                                near_sdk::borsh::BorshDeserialize::try_from_slice(&data)
+                            },
+                        };
+                        quote! {
+                            let #mutability #ident: #ty = near_sdk::env::promise_result(#idx)
+                                .into_result()
+                                .and_then(|data| #invocation.map_err(|_| near_sdk::PromiseError::Deserialization));
+                        }
+                    }
+                    _ => quote! {
+                        let data = match near_sdk::env::promise_result(#idx) {
+                            near_sdk::PromiseResult::Successful(x) => x,
+                            _ => panic!("Callback computation {} was not successful", #idx)
+                        };
+                        let #mutability #ident: #ty = #invocation;
+                    },
+                };
                 quote! {
-                #acc
-                #read_data
-                let #mutability #ident: #ty = #invocation;
-            }
+                    #acc
+                    #deserialization
+                }
             })
     }
 
@@ -218,7 +237,7 @@ impl AttrSigInfo {
                 #acc
                 let #mutability #ident: #ty = (0..near_sdk::env::promise_results_count())
                 .map(|i| {
-                    let data: Vec<u8> = match near_sdk::env::promise_result(i) {
+                    let data = match near_sdk::env::promise_result(i) {
                         near_sdk::PromiseResult::Successful(x) => x,
                         _ => panic!("Callback computation {} was not successful", i)
                     };
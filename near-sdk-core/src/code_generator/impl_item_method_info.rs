@@ -77,18 +77,19 @@ impl ImplItemMethodInfo {
         } else {
             quote! {}
         };
+        let state_key = quote! { near_sdk::contract_state_key!(#struct_type) };
         let body = if matches!(method_type, &MethodType::Init) {
             quote! {
-                if near_sdk::env::state_exists() {
+                if near_sdk::env::state_exists_key(#state_key) {
                     near_sdk::env::panic(b"The contract has already been initialized");
                 }
                 let contract = #struct_type::#ident(#arg_list);
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(#state_key, &contract);
             }
         } else if matches!(method_type, &MethodType::InitIgnoreState) {
             quote! {
                 let contract = #struct_type::#ident(#arg_list);
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(#state_key, &contract);
             }
         } else {
             let contract_deser;
@@ -97,14 +98,14 @@ impl ImplItemMethodInfo {
             if let Some(receiver) = receiver {
                 let mutability = &receiver.mutability;
                 contract_deser = quote! {
-                    let #mutability contract: #struct_type = near_sdk::env::state_read().unwrap_or_default();
+                    let #mutability contract: #struct_type = near_sdk::env::state_read_key(#state_key).unwrap_or_default();
                 };
                 method_invocation = quote! {
                     contract.#ident(#arg_list)
                 };
                 if matches!(method_type, &MethodType::Regular) {
                     contract_ser = quote! {
-                        near_sdk::env::state_write(&contract);
+                        near_sdk::env::state_write_key(#state_key, &contract);
                     };
                 } else {
                     contract_ser = TokenStream2::new();
@@ -179,7 +180,7 @@ impl ImplItemMethodInfo {
             }
         } else {
             quote! {
-             let args = vec![];
+             let args = near_sdk::alloc::vec![];
             }
         };
 
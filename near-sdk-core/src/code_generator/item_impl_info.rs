@@ -61,7 +61,7 @@ mod tests {
             #[no_mangle]
             pub extern "C" fn method() {
                 near_sdk::env::setup_panic_hook();
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method();
             }
         );
@@ -79,7 +79,7 @@ mod tests {
             #[no_mangle]
             pub extern "C" fn method() {
                 near_sdk::env::setup_panic_hook();
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method();
             }
         );
@@ -100,9 +100,9 @@ mod tests {
                 if near_sdk::env::attached_deposit() != 0 {
                     near_sdk::env::panic("Method method doesn't accept deposit".as_bytes());
                 }
-                let mut contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let mut contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method();
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -128,7 +128,7 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from JSON.");
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method(k, );
             }
         );
@@ -160,9 +160,9 @@ mod tests {
                         &near_sdk::env::input().expect("Expected input since method has arguments.")
                     )
                     .expect("Failed to deserialize input from JSON.");
-                    let mut contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                    let mut contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                     contract.method(k, m, );
-                    near_sdk::env::state_write(&contract);
+                    near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
                 }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -193,12 +193,12 @@ mod tests {
                         &near_sdk::env::input().expect("Expected input since method has arguments.")
                     )
                     .expect("Failed to deserialize input from JSON.");
-                    let mut contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                    let mut contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                     let result = contract.method(k, m, );
                     let result =
                         near_sdk::serde_json::to_vec(&result).expect("Failed to serialize the return value using JSON.");
                     near_sdk::env::value_return(&result);
-                    near_sdk::env::state_write(&contract);
+                    near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
                 }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -216,7 +216,7 @@ mod tests {
             #[no_mangle]
             pub extern "C" fn method() {
                 near_sdk::env::setup_panic_hook();
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 let result = contract.method();
                 let result =
                     near_sdk::serde_json::to_vec(&result).expect("Failed to serialize the return value using JSON.");
@@ -246,7 +246,7 @@ mod tests {
                         &near_sdk::env::input().expect("Expected input since method has arguments.")
                     )
                     .expect("Failed to deserialize input from JSON.");
-                    let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                    let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                     contract.method(&k, );
                 }
         );
@@ -274,7 +274,7 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from JSON.");
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method(&mut k, );
             }
         );
@@ -306,19 +306,19 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from JSON.");
-                let data: Vec<u8> = match near_sdk::env::promise_result(0u64) {
+                let data = match near_sdk::env::promise_result(0u64) {
                     near_sdk::PromiseResult::Successful(x) => x,
                     _ => panic!("Callback computation {} was not successful", 0u64)
                 };
                 let mut x: u64 =
                     near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON");
-                let data: Vec<u8> = match near_sdk::env::promise_result(1u64) {
+                let data = match near_sdk::env::promise_result(1u64) {
                     near_sdk::PromiseResult::Successful(x) => x,
                     _ => panic!("Callback computation {} was not successful", 1u64)
                 };
                 let z: Vec<u8> =
                     near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON");
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method(&mut x, y, z, );
             }
         );
@@ -341,19 +341,19 @@ mod tests {
                 if env::current_account_id() != env::predecessor_account_id() {
                     near_sdk::env::panic("Method method is private".as_bytes());
                 }
-                let data: Vec<u8> = match near_sdk::env::promise_result(0u64) {
+                let data = match near_sdk::env::promise_result(0u64) {
                     near_sdk::PromiseResult::Successful(x) => x,
                     _ => panic!("Callback computation {} was not successful", 0u64)
                 };
                 let mut x: u64 =
                     near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON");
-                let data: Vec<u8> = match near_sdk::env::promise_result(1u64) {
+                let data = match near_sdk::env::promise_result(1u64) {
                     near_sdk::PromiseResult::Successful(x) => x,
                     _ => panic!("Callback computation {} was not successful", 1u64)
                 };
                 let y: String =
                     near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON");
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method(&mut x, y, );
             }
         );
@@ -361,6 +361,73 @@ mod tests {
         assert_eq!(expected.to_string(), actual.to_string());
     }
 
+    #[test]
+    fn callback_result_args() {
+        let impl_type: Type = syn::parse_str("Hello").unwrap();
+        let mut method: ImplItemMethod = parse_quote! {
+            #[private] pub fn method(&self, #[callback] x: u64, #[callback_result] #[serializer(borsh)] y: Result<String, PromiseError>) { }
+        };
+        let method_info = ImplItemMethodInfo::new(&mut method, impl_type).unwrap();
+        let actual = method_info.method_wrapper();
+        let expected = quote!(
+            #[cfg(target_arch = "wasm32")]
+            #[no_mangle]
+            pub extern "C" fn method() {
+                near_sdk::env::setup_panic_hook();
+                if env::current_account_id() != env::predecessor_account_id() {
+                    near_sdk::env::panic("Method method is private".as_bytes());
+                }
+                let data = match near_sdk::env::promise_result(0u64) {
+                    near_sdk::PromiseResult::Successful(x) => x,
+                    _ => panic!("Callback computation {} was not successful", 0u64)
+                };
+                let x: u64 =
+                    near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON");
+                let y: Result<String, PromiseError> = near_sdk::env::promise_result(1u64)
+                    .into_result()
+                    .and_then(|data| near_sdk::borsh::BorshDeserialize::try_from_slice(&data).map_err(|_| near_sdk::PromiseError::Deserialization));
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
+                contract.method(x, y, );
+            }
+        );
+        assert_eq!(expected.to_string(), actual.to_string());
+    }
+
+    #[test]
+    fn callback_result_json() {
+        let impl_type: Type = syn::parse_str("Hello").unwrap();
+        let mut method: ImplItemMethod = parse_quote! {
+            #[private] pub fn method(&self, #[callback_result] x: Result<u64, PromiseError>) { }
+        };
+        let method_info = ImplItemMethodInfo::new(&mut method, impl_type).unwrap();
+        let actual = method_info.method_wrapper();
+        let expected = quote!(
+            #[cfg(target_arch = "wasm32")]
+            #[no_mangle]
+            pub extern "C" fn method() {
+                near_sdk::env::setup_panic_hook();
+                if env::current_account_id() != env::predecessor_account_id() {
+                    near_sdk::env::panic("Method method is private".as_bytes());
+                }
+                let x: Result<u64, PromiseError> = near_sdk::env::promise_result(0u64)
+                    .into_result()
+                    .and_then(|data| near_sdk::serde_json::from_slice(&data).map_err(|_| near_sdk::PromiseError::Deserialization));
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
+                contract.method(x, );
+            }
+        );
+        assert_eq!(expected.to_string(), actual.to_string());
+    }
+
+    #[test]
+    fn callback_result_bad_type() {
+        let impl_type: Type = syn::parse_str("Hello").unwrap();
+        let mut method: ImplItemMethod = parse_quote! {
+            #[private] pub fn method(&self, #[callback_result] x: u64) { }
+        };
+        assert!(ImplItemMethodInfo::new(&mut method, impl_type).is_err());
+    }
+
     #[test]
     fn callback_args_vec() {
         let impl_type: Type = syn::parse_str("Hello").unwrap();
@@ -388,14 +455,14 @@ mod tests {
                 .expect("Failed to deserialize input from JSON.");
                 let x: Vec<String> = (0..near_sdk::env::promise_results_count())
                     .map(|i| {
-                        let data: Vec<u8> = match near_sdk::env::promise_result(i) {
+                        let data = match near_sdk::env::promise_result(i) {
                             near_sdk::PromiseResult::Successful(x) => x,
                             _ => panic!("Callback computation {} was not successful", i)
                         };
                         near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON")
                     })
                     .collect();
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method(x, y, );
             }
         );
@@ -428,11 +495,11 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from JSON.");
-                if near_sdk::env::state_exists() {
+                if near_sdk::env::state_exists_key(near_sdk::contract_state_key!(Hello)) {
                     near_sdk::env::panic(b"The contract has already been initialized");
                 }
                 let contract = Hello::method(&mut k,);
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -465,7 +532,7 @@ mod tests {
                 )
                 .expect("Failed to deserialize input from JSON.");
                 let contract = Hello::method(&mut k,);
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -495,11 +562,11 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from JSON.");
-                if near_sdk::env::state_exists() {
+                if near_sdk::env::state_exists_key(near_sdk::contract_state_key!(Hello)) {
                     near_sdk::env::panic(b"The contract has already been initialized");
                 }
                 let contract = Hello::method(&mut k,);
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -531,12 +598,12 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from Borsh.");
-                let mut contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let mut contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 let result = contract.method(k, m, );
                 let result = near_sdk::borsh::BorshSerialize::try_to_vec(&result)
                     .expect("Failed to serialize the return value using Borsh.");
                 near_sdk::env::value_return(&result);
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -566,19 +633,19 @@ mod tests {
                     &near_sdk::env::input().expect("Expected input since method has arguments.")
                 )
                 .expect("Failed to deserialize input from Borsh.");
-                let data: Vec<u8> = match near_sdk::env::promise_result(0u64) {
+                let data = match near_sdk::env::promise_result(0u64) {
                     near_sdk::PromiseResult::Successful(x) => x,
                     _ => panic!("Callback computation {} was not successful", 0u64)
                 };
                 let mut x: u64 = near_sdk::borsh::BorshDeserialize::try_from_slice(&data)
                     .expect("Failed to deserialize callback using Borsh");
-                let data: Vec<u8> = match near_sdk::env::promise_result(1u64) {
+                let data = match near_sdk::env::promise_result(1u64) {
                     near_sdk::PromiseResult::Successful(x) => x,
                     _ => panic!("Callback computation {} was not successful", 1u64)
                 };
                 let z: Vec<u8> =
                     near_sdk::serde_json::from_slice(&data).expect("Failed to deserialize callback using JSON");
-                let contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method(&mut x, y, z, );
             }
         );
@@ -596,9 +663,9 @@ mod tests {
             #[no_mangle]
             pub extern "C" fn method() {
                 near_sdk::env::setup_panic_hook();
-                let mut contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let mut contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.method();
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -621,9 +688,9 @@ mod tests {
                 if near_sdk::env::attached_deposit() != 0 {
                     near_sdk::env::panic("Method private_method doesn't accept deposit".as_bytes());
                 }
-                let mut contract: Hello = near_sdk::env::state_read().unwrap_or_default();
+                let mut contract: Hello = near_sdk::env::state_read_key(near_sdk::contract_state_key!(Hello)).unwrap_or_default();
                 contract.private_method();
-                near_sdk::env::state_write(&contract);
+                near_sdk::env::state_write_key(near_sdk::contract_state_key!(Hello), &contract);
             }
         );
         assert_eq!(expected.to_string(), actual.to_string());
@@ -1,4 +1,5 @@
-use syn::ItemStruct;
+use syn::export::TokenStream2;
+use syn::{ItemStruct, Lit, Meta, NestedMeta};
 
 #[cfg(not(target_arch = "wasm"))]
 pub fn generate_proxy_struct(input: &ItemStruct) -> proc_macro2::TokenStream {
@@ -17,3 +18,44 @@ pub fn generate_proxy_struct(input: &ItemStruct) -> proc_macro2::TokenStream {
 pub fn generate_proxy_struct(input: &ItemStruct) {
     quote! {}
 }
+
+/// Generates the implementation of `near_sdk::ContractState` for the contract struct. The state
+/// key can be overridden with `#[near_bindgen(state_key = "...")]`.
+pub fn generate_contract_state_impl(
+    input: &ItemStruct,
+    args: &[NestedMeta],
+) -> syn::Result<TokenStream2> {
+    use quote::quote;
+    let mut state_key = None;
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("state_key") => {
+                match &nv.lit {
+                    Lit::Str(s) if !s.value().is_empty() => {
+                        let key = proc_macro2::Literal::byte_string(s.value().as_bytes());
+                        state_key = Some(quote! { const STATE_KEY: &'static [u8] = #key; });
+                    }
+                    lit => {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "state_key should be a non-empty string literal.",
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    arg,
+                    "Unsupported argument, only `state_key = \"...\"` is supported on structs.",
+                ))
+            }
+        }
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics near_sdk::ContractState for #ident #ty_generics #where_clause {
+            #state_key
+        }
+    })
+}
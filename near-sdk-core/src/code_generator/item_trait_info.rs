@@ -6,16 +6,23 @@ impl ItemTraitInfo {
     /// Generate code that wrapps external calls.
     pub fn wrapped_module(&self) -> TokenStream2 {
         let mut result = TokenStream2::new();
+        let mut typed = TokenStream2::new();
         for method in &self.methods {
             result.extend(method.method_wrapper());
+            typed.extend(method.typed_method_wrapper());
         }
         let mod_name = &self.mod_name;
         quote! {
            pub mod #mod_name {
                 use super::*;
                 use near_sdk::{Gas, Balance, AccountId, Promise};
-                use std::string::ToString;
+                use near_sdk::alloc::string::ToString;
                 #result
+                /// Same functions that return `TypedPromise` and `TypedCallback`.
+                pub mod typed_calls {
+                    use super::*;
+                    #typed
+                }
             }
         }
     }
@@ -54,10 +61,10 @@ mod tests {
             pub mod external_cross_contract {
                 use super::*;
                 use near_sdk::{Gas, Balance, AccountId, Promise};
-                use std::string::ToString;
-                pub fn merge_sort<T: ToString>(
+                use near_sdk::alloc::string::ToString;
+                pub fn merge_sort<__AccountId: ToString>(
                     arr: Vec<u8>,
-                    __account_id: &T,
+                    __account_id: &__AccountId,
                     __balance: near_sdk::Balance,
                     __gas: near_sdk::Gas
                 ) -> near_sdk::Promise {
@@ -76,8 +83,8 @@ mod tests {
                         __gas,
                     )
                 }
-                pub fn merge<T: ToString>(__account_id: &T, __balance: near_sdk::Balance, __gas: near_sdk::Gas) -> near_sdk::Promise {
-                    let args = vec![];
+                pub fn merge<__AccountId: ToString>(__account_id: &__AccountId, __balance: near_sdk::Balance, __gas: near_sdk::Gas) -> near_sdk::Promise {
+                    let args = near_sdk::alloc::vec![];
                     near_sdk::Promise::new(AccountId::new_unchecked(__account_id.to_string())).function_call(
                         b"merge".to_vec(),
                         args,
@@ -85,6 +92,25 @@ mod tests {
                         __gas,
                     )
                 }
+                /// Same functions that return `TypedPromise` and `TypedCallback`.
+                pub mod typed_calls {
+                    use super::*;
+                    pub fn merge_sort<__AccountId: ToString>(
+                        arr: Vec<u8>,
+                        __account_id: &__AccountId,
+                        __balance: near_sdk::Balance,
+                        __gas: near_sdk::Gas
+                    ) -> near_sdk::TypedPromise<Vec<u8> > {
+                        near_sdk::TypedPromise::new(super::merge_sort(arr, __account_id, __balance, __gas))
+                    }
+                    pub fn merge<__AccountId: ToString>(
+                        __account_id: &__AccountId,
+                        __balance: near_sdk::Balance,
+                        __gas: near_sdk::Gas
+                    ) -> near_sdk::TypedCallback<(Vec<u8>, Vec<u8>), Vec<u8> > {
+                        near_sdk::TypedCallback::new(super::merge(__account_id, __balance, __gas))
+                    }
+                }
             }
         };
         assert_eq!(actual.to_string(), expected.to_string());
@@ -107,10 +133,10 @@ mod tests {
           pub mod test_ext {
             use super::*;
             use near_sdk::{Gas, Balance, AccountId, Promise};
-            use std::string::ToString;
-            pub fn test<T: ToString>(
+            use near_sdk::alloc::string::ToString;
+            pub fn test<__AccountId: ToString>(
                 v: Vec<String>,
-                __account_id: &T,
+                __account_id: &__AccountId,
                 __balance: near_sdk::Balance,
                 __gas: near_sdk::Gas
             ) -> near_sdk::Promise {
@@ -128,6 +154,18 @@ mod tests {
                     __gas,
                 )
             }
+            /// Same functions that return `TypedPromise` and `TypedCallback`.
+            pub mod typed_calls {
+                use super::*;
+                pub fn test<__AccountId: ToString>(
+                    v: Vec<String>,
+                    __account_id: &__AccountId,
+                    __balance: near_sdk::Balance,
+                    __gas: near_sdk::Gas
+                ) -> near_sdk::TypedPromise<Vec<String> > {
+                    near_sdk::TypedPromise::new(super::test(v, __account_id, __balance, __gas))
+                }
+            }
         }
         };
         assert_eq!(actual.to_string(), expected.to_string());
@@ -1,9 +1,12 @@
 use crate::{
-    info_extractor::{InputStructType, SerializerType, TraitItemMethodInfo},
-    AttrSigInfo,
+    info_extractor::{
+        callback_result_ok_type, InputStructType, SerializerType, TraitItemMethodInfo,
+    },
+    AttrSigInfo, BindgenArgType,
 };
 use quote::quote;
 use syn::export::TokenStream2;
+use syn::{GenericArgument, PathArguments, ReturnType, Type};
 
 impl TraitItemMethodInfo {
     /// Generate code that wraps the method.
@@ -16,7 +19,7 @@ impl TraitItemMethodInfo {
             &self.attr_sig_info.result_serializer,
         );
         quote! {
-            pub fn #ident<T: ToString>(#pat_type_list __account_id: &T, __balance: near_sdk::Balance, __gas: near_sdk::Gas) -> near_sdk::Promise {
+            pub fn #ident<__AccountId: ToString>(#pat_type_list __account_id: &__AccountId, __balance: near_sdk::Balance, __gas: near_sdk::Gas) -> near_sdk::Promise {
                 #serialize
                 near_sdk::Promise::new(AccountId::new_unchecked(__account_id.to_string()))
                 .function_call(
@@ -29,13 +32,65 @@ impl TraitItemMethodInfo {
         }
     }
 
+    /// Generate a wrapper that returns `TypedPromise` or `TypedCallback` instead of `Promise`, so
+    /// that the types of callbacks are checked at compile time. Returns `None` for methods that
+    /// mix `#[callback_vec]` with other callback arguments.
+    pub fn typed_method_wrapper(&self) -> Option<TokenStream2> {
+        let ident = &self.attr_sig_info.ident;
+        let pat_type_list = self.attr_sig_info.pat_type_list();
+        let arg_idents = self.attr_sig_info.input_args().map(|arg| &arg.ident);
+        let result_ty = match &self.attr_sig_info.returns {
+            ReturnType::Default => quote! { () },
+            ReturnType::Type(_, ty) => {
+                let ty = promise_or_value_inner_type(ty).unwrap_or(ty);
+                quote! { #ty }
+            }
+        };
+
+        let mut callback_tys = vec![];
+        let mut callback_vec_ty = None;
+        for arg in &self.attr_sig_info.args {
+            match arg.bindgen_ty {
+                BindgenArgType::CallbackArg => callback_tys.push(&arg.ty),
+                BindgenArgType::CallbackResultArg => callback_tys
+                    .push(callback_result_ok_type(&arg.ty).expect("checked by the arg info")),
+                BindgenArgType::CallbackArgVec => callback_vec_ty = Some(&arg.ty),
+                BindgenArgType::Regular => {}
+            }
+        }
+        let (return_ty, constructor) = match (callback_vec_ty, callback_tys.as_slice()) {
+            (None, []) => (
+                quote! { near_sdk::TypedPromise<#result_ty> },
+                quote! { near_sdk::TypedPromise::new },
+            ),
+            (None, [ty]) => (
+                quote! { near_sdk::TypedCallback<#ty, #result_ty> },
+                quote! { near_sdk::TypedCallback::new },
+            ),
+            (None, tys) => (
+                quote! { near_sdk::TypedCallback<(#(#tys),*), #result_ty> },
+                quote! { near_sdk::TypedCallback::new },
+            ),
+            (Some(ty), []) => (
+                quote! { near_sdk::TypedCallback<near_sdk::Joined<#ty>, #result_ty> },
+                quote! { near_sdk::TypedCallback::new },
+            ),
+            (Some(_), _) => return None,
+        };
+        Some(quote! {
+            pub fn #ident<__AccountId: ToString>(#pat_type_list __account_id: &__AccountId, __balance: near_sdk::Balance, __gas: near_sdk::Gas) -> #return_ty {
+                #constructor(super::#ident(#(#arg_idents,)* __account_id, __balance, __gas))
+            }
+        })
+    }
+
     pub fn generate_serialier(
         attr_sig_info: &AttrSigInfo,
         serializer: &SerializerType,
     ) -> TokenStream2 {
         let has_input_args = attr_sig_info.input_args().next().is_some();
         if !has_input_args {
-            return quote! { let args = vec![]; };
+            return quote! { let args = near_sdk::alloc::vec![]; };
         }
         let struct_decl = attr_sig_info.input_struct(InputStructType::Serialization);
         let constructor_call = attr_sig_info.constructor_expr();
@@ -56,3 +111,21 @@ impl TraitItemMethodInfo {
         }
     }
 }
+
+/// Extracts `T` from `PromiseOrValue<T>`, since the result of such method has type `T`.
+fn promise_or_value_inner_type(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(type_path) => type_path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "PromiseOrValue" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
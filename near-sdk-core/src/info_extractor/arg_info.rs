@@ -2,13 +2,15 @@ use crate::info_extractor::serializer_attr::SerializerAttr;
 use crate::info_extractor::SerializerType;
 use quote::ToTokens;
 use syn::export::Span;
-use syn::{Attribute, Error, Ident, Pat, PatType, Token, Type};
+use syn::{Attribute, Error, GenericArgument, Ident, Pat, PatType, PathArguments, Token, Type};
 
 pub enum BindgenArgType {
     /// Argument that we read from `env::input()`.
     Regular,
     /// An argument that we read from a single `env::promise_result()`.
     CallbackArg,
+    /// An argument that we read from a single `env::promise_result()` which handles the error.
+    CallbackResultArg,
     /// An argument that we read from all `env::promise_result()`.
     CallbackArgVec,
 }
@@ -74,6 +76,16 @@ impl ArgInfo {
                 "callback" => {
                     bindgen_ty = BindgenArgType::CallbackArg;
                 }
+                "callback_result" => {
+                    if callback_result_ok_type(&ty).is_none() {
+                        return Err(Error::new_spanned(
+                            &original.ty,
+                            "Arguments decorated with #[callback_result] should have type \
+                             `Result<T, PromiseError>`.",
+                        ));
+                    }
+                    bindgen_ty = BindgenArgType::CallbackResultArg;
+                }
                 "callback_vec" => {
                     bindgen_ty = BindgenArgType::CallbackArgVec;
                 }
@@ -89,7 +101,10 @@ impl ArgInfo {
 
         original.attrs.retain(|attr| {
             let attr_str = attr.path.to_token_stream().to_string();
-            attr_str != "callback" && attr_str != "callback_vec" && attr_str != "serializer"
+            attr_str != "callback"
+                && attr_str != "callback_result"
+                && attr_str != "callback_vec"
+                && attr_str != "serializer"
         });
 
         Ok(Self {
@@ -106,3 +121,21 @@ impl ArgInfo {
         })
     }
 }
+
+/// Extracts `T` from the `Result<T, E>` type of a `#[callback_result]` argument.
+pub(crate) fn callback_result_ok_type(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => type_path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 2 => match &args.args[0] {
+            GenericArgument::Type(ok_ty) => Some(ok_ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -2,6 +2,7 @@ mod serializer_attr;
 pub use serializer_attr::SerializerAttr;
 
 mod arg_info;
+pub(crate) use arg_info::callback_result_ok_type;
 pub use arg_info::{ArgInfo, BindgenArgType};
 
 mod attr_sig_info;
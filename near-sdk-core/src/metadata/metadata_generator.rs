@@ -1,3 +1,4 @@
+use crate::info_extractor::callback_result_ok_type;
 use crate::{BindgenArgType, ImplItemMethodInfo, InputStructType, MethodType, SerializerType};
 
 use quote::quote;
@@ -15,7 +16,7 @@ impl ImplItemMethodInfo {
     /// will produce this struct:
     /// ```ignore
     /// near_sdk::MethodMetadata {
-    ///     name: "f3".to_string(),
+    ///     name: "f3".into(),
     ///     is_view: false,
     ///     is_init: false,
     ///     args: {
@@ -27,7 +28,7 @@ impl ImplItemMethodInfo {
     ///         }
     ///         Some(Input::schema_container())
     ///     },
-    ///     callbacks: vec![],
+    ///     callbacks: near_sdk::alloc::vec![],
     ///     callbacks_vec: None,
     ///     result: Some(Result < IsOk, Error > ::schema_container())
     /// }
@@ -67,11 +68,15 @@ impl ImplItemMethodInfo {
             .args
             .iter()
             .filter(|arg| match arg.bindgen_ty {
-                BindgenArgType::CallbackArg => true,
+                BindgenArgType::CallbackArg | BindgenArgType::CallbackResultArg => true,
                 _ => false,
             })
             .map(|arg| {
-                let ty = &arg.ty;
+                let ty = match arg.bindgen_ty {
+                    BindgenArgType::CallbackResultArg => callback_result_ok_type(&arg.ty)
+                        .expect("Type of #[callback_result] is checked by the info extractor"),
+                    _ => &arg.ty,
+                };
                 quote! {
                     #ty::schema_container()
                 }
@@ -114,11 +119,11 @@ impl ImplItemMethodInfo {
 
         quote! {
              near_sdk::MethodMetadata {
-                 name: #method_name_str.to_string(),
+                 name: #method_name_str.into(),
                  is_view: #is_view,
                  is_init: #is_init,
                  args: #args,
-                 callbacks: vec![#(#callbacks),*],
+                 callbacks: near_sdk::alloc::vec![#(#callbacks),*],
                  callbacks_vec: #callbacks_vec,
                  result: #result
              }
@@ -57,7 +57,7 @@ impl MetadataVisitor {
             pub extern "C" fn metadata() {
                 #panic_hook
                 use borsh::*;
-                let metadata = near_sdk::Metadata::new(vec![
+                let metadata = near_sdk::Metadata::new(near_sdk::alloc::vec![
                     #(#methods),*
                 ]);
                 let data = near_sdk::borsh::BorshSerialize::try_to_vec(&metadata).expect("Failed to serialize the metadata using Borsh");
@@ -100,18 +100,18 @@ mod tests {
             pub extern "C" fn metadata() {
                 near_sdk::env::setup_panic_hook();
                 use borsh::*;
-                let metadata = near_sdk::Metadata::new(vec![
+                let metadata = near_sdk::Metadata::new(near_sdk::alloc::vec![
                     near_sdk::MethodMetadata {
-                        name: "f1".to_string(),
+                        name: "f1".into(),
                         is_view: true,
                         is_init: false,
                         args: None,
-                        callbacks: vec![],
+                        callbacks: near_sdk::alloc::vec![],
                         callbacks_vec: None,
                         result: None
                     },
                     near_sdk::MethodMetadata {
-                        name: "f2".to_string(),
+                        name: "f2".into(),
                         is_view: false,
                         is_init: false,
                         args: {
@@ -124,12 +124,12 @@ mod tests {
                             }
                             Some(Input::schema_container())
                         },
-                        callbacks: vec![],
+                        callbacks: near_sdk::alloc::vec![],
                         callbacks_vec: None,
                         result: None
                     },
                     near_sdk::MethodMetadata {
-                        name: "f3".to_string(),
+                        name: "f3".into(),
                         is_view: false,
                         is_init: false,
                         args: {
@@ -142,7 +142,7 @@ mod tests {
                             }
                             Some(Input::schema_container())
                         },
-                        callbacks: vec![],
+                        callbacks: near_sdk::alloc::vec![],
                         callbacks_vec: None,
                         result: Some(Result < IsOk, Error > ::schema_container())
                     }
@@ -7,17 +7,34 @@ use near_sdk_core::*;
 use proc_macro2::Span;
 use quote::quote;
 use syn::visit::Visit;
-use syn::{File, ItemEnum, ItemImpl, ItemStruct, ItemTrait};
+use syn::{AttributeArgs, File, ItemEnum, ItemImpl, ItemStruct, ItemTrait};
 
 #[proc_macro_attribute]
-pub fn near_bindgen(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn near_bindgen(attr: TokenStream, item: TokenStream) -> TokenStream {
     if let Ok(input) = syn::parse::<ItemStruct>(item.clone()) {
+        let args = syn::parse_macro_input!(attr as AttributeArgs);
+        let contract_state = match generate_contract_state_impl(&input, &args) {
+            Ok(x) => x,
+            Err(err) => {
+                return err.to_compile_error().into();
+            }
+        };
         let struct_proxy = generate_proxy_struct(&input);
         TokenStream::from(quote! {
             #input
+            #contract_state
             #struct_proxy
         })
     } else if let Ok(mut input) = syn::parse::<ItemImpl>(item) {
+        if !attr.is_empty() {
+            return TokenStream::from(
+                syn::Error::new(
+                    Span::call_site(),
+                    "near_bindgen arguments are only supported on type declarations.",
+                )
+                .to_compile_error(),
+            );
+        }
         let item_impl_info = match ItemImplInfo::new(&mut input) {
             Ok(x) => x,
             Err(err) => {
@@ -83,6 +100,13 @@ pub fn callback(_attr: TokenStream, item: TokenStream) -> TokenStream {
     item
 }
 
+/// `callback_result` is a marker attribute it does not generate code by itself.
+/// The argument receives `Err(PromiseError)` instead of panicking when the promise failed.
+#[proc_macro_attribute]
+pub fn callback_result(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
 /// `callback_args_vec` is a marker attribute it does not generate code by itself.
 #[proc_macro_attribute]
 pub fn callback_vec(_attr: TokenStream, item: TokenStream) -> TokenStream {
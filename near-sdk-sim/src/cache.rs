@@ -1,12 +1,18 @@
 use crate::types::CompiledContractCache;
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
+/// Number of files written by this process, used to name temporary files.
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
 /// This provides a disc cache for compiled contracts.
 /// The cached contracts are located `CARGO_MANIFEST_DIR/target/contract_cache`.
+///
+/// The cache can be shared by test processes running in parallel: files are written to a
+/// temporary file first and then renamed, so readers never see a partially written contract.
 #[derive(Clone, Default)]
 pub struct ContractCache {
     data: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
@@ -26,21 +32,29 @@ impl ContractCache {
         Path::new(&s).join("target").join("contract_cache")
     }
 
-    fn open_file(&self, key: &[u8]) -> std::io::Result<File> {
-        let path = self.get_path(key);
-        // Ensure that the parent path exists
-        let prefix = path.parent().unwrap();
-        std::fs::create_dir_all(prefix).unwrap();
-        // Ensure we can read, write, and create file if it doesn't exist
-        OpenOptions::new().read(true).write(true).create(true).open(path)
-    }
-
     fn get_path(&self, key: &[u8]) -> PathBuf {
         ContractCache::path().join(key_to_b58(key))
     }
 
-    fn file_exists(&self, key: &[u8]) -> bool {
-        self.get_path(key).exists()
+    fn write_file(&self, key: &[u8], value: &[u8]) -> std::io::Result<()> {
+        let path = self.get_path(key);
+        // The file is always replaced, since a file left by an older version or an interrupted
+        // write may hold a different contract.
+        // Ensure that the parent path exists
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let tmp_name = format!(
+            "{}.{}.{}.tmp",
+            key_to_b58(key),
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        );
+        let tmp_path = path.with_file_name(tmp_name);
+        std::fs::write(&tmp_path, value)?;
+        match std::fs::rename(&tmp_path, &path) {
+            // Renaming fails on some platforms if another process created the file meanwhile.
+            Err(_) if path.exists() => std::fs::remove_file(&tmp_path),
+            res => res,
+        }
     }
 
     pub fn insert(&self, key: &[u8], value: &[u8]) -> Option<Vec<u8>> {
@@ -60,25 +74,21 @@ impl ContractCache {
 impl CompiledContractCache for ContractCache {
     fn put(&self, key: &[u8], value: &[u8]) -> Result<(), std::io::Error> {
         self.insert(key, value);
-        let mut file = self.open_file(key).expect("File failed to open");
-        let metadata = file.metadata()?;
-        if metadata.len() != value.len() as u64 {
-            file.write_all(value)?;
-        }
-        Ok(())
+        self.write_file(key, value)
     }
 
     fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, std::io::Error> {
         if (*self.data).lock().unwrap().contains_key(key) {
             return Ok(self.get(key));
-        } else if self.file_exists(key) {
-            let mut file = self.open_file(key)?;
-            let mut contents = vec![];
-            file.read_to_end(&mut contents)?;
-            self.insert(key, &contents);
-            return Ok(Some(contents));
         }
-        Ok(None)
+        match std::fs::read(self.get_path(key)) {
+            Ok(contents) => {
+                self.insert(key, &contents);
+                Ok(Some(contents))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
     }
 }
 
@@ -89,3 +99,45 @@ pub fn create_cache() -> ContractCache {
 pub fn cache_to_arc(cache: &ContractCache) -> Arc<ContractCache> {
     cache.to_arc()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_writers() {
+        let key = format!("concurrent_writers.{}", std::process::id()).into_bytes();
+        let value = vec![7u8; 100_000];
+        let path = ContractCache::new().get_path(&key);
+        // A file of the same size with other contents is replaced.
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, vec![0u8; value.len()]).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let (key, value) = (key.clone(), value.clone());
+                std::thread::spawn(move || {
+                    let cache = ContractCache::new();
+                    for _ in 0..10 {
+                        CompiledContractCache::put(&cache, &key, &value).unwrap();
+                        let contents = std::fs::read(cache.get_path(&key)).unwrap();
+                        assert_eq!(contents, value);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), value);
+        let tmp_prefix = format!("{}.", key_to_b58(&key));
+        let leftovers = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(&tmp_prefix)
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,492 @@
+//! Property-based testing of contracts with random sequences of method calls. Requires the
+//! `fuzz` feature.
+//!
+//! A [`Fuzzer`] calls the methods of a contract described by [`Method`]s from random callers and
+//! checks invariants after every call. When an invariant is violated, the sequence of calls is
+//! shrunk to a minimal one which still violates it.
+//!
+//! # Example
+//!
+//! ```
+//! use near_sdk::serde_json::json;
+//! use near_sdk_sim::fuzz::{choose, Fuzzer, Method};
+//! use near_sdk_sim::{init_simulator, to_yocto};
+//!
+//! let root = init_simulator(None);
+//! let status = root.deploy(
+//!     include_bytes!("../../examples/status-message/res/status_message.wasm").as_ref(),
+//!     "status".parse().unwrap(),
+//!     to_yocto("35"),
+//! );
+//! let alice = root.create_user("alice".parse().unwrap(), to_yocto("100"));
+//!
+//! let status_id = status.account_id();
+//! Fuzzer::new(status.account_id(), vec![root, alice])
+//!     .method(Method::new("set_status", |g| json!({ "message": choose(g, &["hi", "bye"]) })))
+//!     .invariant("only generated messages are stored", move |users| {
+//!         users.iter().all(|user| {
+//!             let args = json!({ "account_id": user.account_id() }).to_string();
+//!             let status: Option<String> =
+//!                 user.view(status_id.clone(), "get_status", args.as_bytes()).unwrap_json();
+//!             status.map_or(true, |status| status == "hi" || status == "bye")
+//!         })
+//!     })
+//!     .runs(2)
+//!     .steps(5)
+//!     .run();
+//! ```
+
+use core::fmt;
+
+use near_sdk::serde_json::Value;
+use near_sdk::AccountId;
+use quickcheck::{Arbitrary, StdGen};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+use crate::runtime::Snapshot;
+use crate::types::{Balance, Gas};
+use crate::{UserAccount, DEFAULT_GAS};
+
+/// Source of randomness passed to the generators of arguments and deposits. Values can be
+/// generated with `Arbitrary::arbitrary(g)`, which produces small integers, [`choose`] or the
+/// methods of `RngCore`.
+pub type FuzzGen = StdGen<StdRng>;
+
+/// Size passed to `Arbitrary` implementations, e.g. the upper bound of generated integers.
+const GEN_SIZE: usize = 100;
+/// Number of sequences executed while shrinking a failing sequence.
+const MAX_SHRINK_RUNS: usize = 1000;
+
+/// Picks a random element of the slice. Panics if the slice is empty.
+pub fn choose<'a, T>(g: &mut FuzzGen, items: &'a [T]) -> &'a T {
+    assert!(!items.is_empty(), "Cannot choose from an empty slice");
+    &items[(g.next_u64() % items.len() as u64) as usize]
+}
+
+/// A contract method called by the fuzzer, with the generators of its arguments and deposit.
+pub struct Method {
+    name: String,
+    args: Box<dyn Fn(&mut FuzzGen) -> Value + Send + Sync>,
+    deposit: Box<dyn Fn(&mut FuzzGen) -> Balance + Send + Sync>,
+    gas: Gas,
+    weight: u32,
+}
+
+impl Method {
+    /// Creates a method called with JSON arguments from the generator, without a deposit.
+    pub fn new(name: &str, args: impl Fn(&mut FuzzGen) -> Value + Send + Sync + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            args: Box::new(args),
+            deposit: Box::new(|_| 0),
+            gas: DEFAULT_GAS,
+            weight: 1,
+        }
+    }
+
+    /// Attaches a deposit from the generator to the calls, e.g. `|_| 1` for methods requiring
+    /// one yoctoNEAR.
+    pub fn deposit(
+        mut self,
+        deposit: impl Fn(&mut FuzzGen) -> Balance + Send + Sync + 'static,
+    ) -> Self {
+        self.deposit = Box::new(deposit);
+        self
+    }
+
+    /// Sets the prepaid gas of the calls, `DEFAULT_GAS` by default.
+    pub fn gas(mut self, gas: Gas) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Sets how often the method is called relative to other methods, 1 by default.
+    pub fn weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// A call in a generated sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzCall {
+    /// Index of the caller in the users passed to [`Fuzzer::new`].
+    pub caller: usize,
+    pub method: String,
+    pub args: Value,
+    pub deposit: Balance,
+    pub gas: Gas,
+}
+
+/// A sequence of calls which violates an invariant, shrunk to a minimal one.
+#[derive(Debug, Clone)]
+pub struct FuzzFailure {
+    /// Seed of the run which found the violation, to reproduce it with [`Fuzzer::seed`].
+    pub seed: u64,
+    pub invariant: String,
+    pub calls: Vec<FuzzCall>,
+    /// Account IDs of the callers.
+    pub callers: Vec<AccountId>,
+}
+
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Invariant \"{}\" violated after {} calls (seed {}):",
+            self.invariant,
+            self.calls.len(),
+            self.seed
+        )?;
+        for call in &self.calls {
+            writeln!(
+                f,
+                "  {}.{}({}) deposit {}",
+                self.callers[call.caller], call.method, call.args, call.deposit
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Invariant {
+    name: String,
+    check: Box<dyn Fn(&[UserAccount]) -> bool + Send + Sync>,
+}
+
+/// Index of the call after which an invariant was violated, and the name of the invariant.
+type Violation = (usize, String);
+
+/// Calls the methods of a contract in random sequences and checks invariants after every call.
+///
+/// All callers must share the runtime, e.g. be created by the same root account. The state of the
+/// runtime is restored after each sequence, calls which fail don't stop the sequence.
+pub struct Fuzzer {
+    contract_id: AccountId,
+    callers: Vec<UserAccount>,
+    methods: Vec<Method>,
+    invariants: Vec<Invariant>,
+    runs: usize,
+    steps: usize,
+    seed: u64,
+}
+
+impl Fuzzer {
+    pub fn new(contract_id: AccountId, callers: Vec<UserAccount>) -> Self {
+        assert!(!callers.is_empty(), "At least one caller is required");
+        Self {
+            contract_id,
+            callers,
+            methods: vec![],
+            invariants: vec![],
+            runs: 100,
+            steps: 20,
+            seed: rand::random(),
+        }
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Adds a check of the state, which is called with the callers after every call.
+    pub fn invariant(
+        mut self,
+        name: &str,
+        check: impl Fn(&[UserAccount]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.invariants.push(Invariant { name: name.to_string(), check: Box::new(check) });
+        self
+    }
+
+    /// Number of sequences to execute, 100 by default.
+    pub fn runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    /// Number of calls in each sequence, 20 by default.
+    pub fn steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Seed of the first sequence, random by default. The seed of a failing sequence is reported
+    /// in the [`FuzzFailure`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Executes the sequences and returns the shrunk sequence of the first violation.
+    pub fn check(&self) -> Result<(), FuzzFailure> {
+        let snapshot = self.callers[0].borrow_runtime().snapshot();
+        let mut result = Ok(());
+        for run in 0..self.runs {
+            let seed = self.seed.wrapping_add(run as u64);
+            let calls = self.generate(seed);
+            if let Some(violation) = self.execute(&snapshot, &calls) {
+                result = Err(self.shrink(&snapshot, calls, violation, seed));
+                break;
+            }
+        }
+        self.callers[0].borrow_runtime_mut().restore(&snapshot);
+        result
+    }
+
+    /// Executes the sequences and panics with the shrunk sequence of the first violation.
+    pub fn run(&self) {
+        if let Err(failure) = self.check() {
+            panic!("{}", failure);
+        }
+    }
+
+    fn generate(&self, seed: u64) -> Vec<FuzzCall> {
+        assert!(!self.methods.is_empty(), "At least one method is required");
+        let mut g = StdGen::new(StdRng::seed_from_u64(seed), GEN_SIZE);
+        let total_weight: u64 = self.methods.iter().map(|method| method.weight as u64).sum();
+        (0..self.steps)
+            .map(|_| {
+                let mut target = g.next_u64() % total_weight;
+                let method = self
+                    .methods
+                    .iter()
+                    .find(|method| {
+                        let found = target < method.weight as u64;
+                        target = target.saturating_sub(method.weight as u64);
+                        found
+                    })
+                    .unwrap();
+                FuzzCall {
+                    caller: (g.next_u64() % self.callers.len() as u64) as usize,
+                    method: method.name.clone(),
+                    args: (method.args)(&mut g),
+                    deposit: (method.deposit)(&mut g),
+                    gas: method.gas,
+                }
+            })
+            .collect()
+    }
+
+    fn execute(&self, snapshot: &Snapshot, calls: &[FuzzCall]) -> Option<Violation> {
+        self.callers[0].borrow_runtime_mut().restore(snapshot);
+        for (step, call) in calls.iter().enumerate() {
+            self.callers[call.caller].call(
+                self.contract_id.clone(),
+                &call.method,
+                call.args.to_string().as_bytes(),
+                call.gas,
+                call.deposit,
+            );
+            if let Some(invariant) =
+                self.invariants.iter().find(|invariant| !(invariant.check)(&self.callers))
+            {
+                return Some((step, invariant.name.clone()));
+            }
+        }
+        None
+    }
+
+    fn shrink(
+        &self,
+        snapshot: &Snapshot,
+        mut calls: Vec<FuzzCall>,
+        (mut step, mut invariant): Violation,
+        seed: u64,
+    ) -> FuzzFailure {
+        calls.truncate(step + 1);
+        let mut runs = 0;
+        'shrink: loop {
+            for candidate in shrink_calls(&calls) {
+                if runs == MAX_SHRINK_RUNS {
+                    break 'shrink;
+                }
+                runs += 1;
+                if let Some(violation) = self.execute(snapshot, &candidate) {
+                    calls = candidate;
+                    step = violation.0;
+                    invariant = violation.1;
+                    calls.truncate(step + 1);
+                    continue 'shrink;
+                }
+            }
+            break;
+        }
+        FuzzFailure {
+            seed,
+            invariant,
+            calls,
+            callers: self.callers.iter().map(|caller| caller.account_id()).collect(),
+        }
+    }
+}
+
+/// Smaller variants of the sequence: with calls removed, then with simpler callers, deposits and
+/// arguments.
+fn shrink_calls(calls: &[FuzzCall]) -> Vec<Vec<FuzzCall>> {
+    let mut candidates = vec![];
+    let mut size = calls.len() / 2;
+    while size > 0 {
+        for start in (0..calls.len()).step_by(size) {
+            let mut candidate = calls[..start].to_vec();
+            candidate.extend_from_slice(&calls[(start + size).min(calls.len())..]);
+            candidates.push(candidate);
+        }
+        size /= 2;
+    }
+    for (i, call) in calls.iter().enumerate() {
+        let mut replace = |call: FuzzCall| {
+            let mut candidate = calls.to_vec();
+            candidate[i] = call;
+            candidates.push(candidate);
+        };
+        for caller in 0..call.caller {
+            replace(FuzzCall { caller, ..call.clone() });
+        }
+        for deposit in call.deposit.shrink() {
+            replace(FuzzCall { deposit, ..call.clone() });
+        }
+        for args in shrink_value(&call.args) {
+            replace(FuzzCall { args, ..call.clone() });
+        }
+    }
+    candidates
+}
+
+/// Smaller variants of a JSON value. Integers and decimal strings, e.g. `U128`, are shrunk
+/// towards zero and arrays lose elements.
+fn shrink_value(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Number(number) => match number.as_u64() {
+            Some(number) => number.shrink().map(Value::from).collect(),
+            None => vec![],
+        },
+        Value::String(string) => match string.parse::<u128>() {
+            Ok(number) => number.shrink().map(|number| Value::String(number.to_string())).collect(),
+            Err(_) => vec![],
+        },
+        Value::Array(items) => {
+            let mut candidates = vec![];
+            for i in 0..items.len() {
+                let mut candidate = items.clone();
+                candidate.remove(i);
+                candidates.push(Value::Array(candidate));
+            }
+            for (i, item) in items.iter().enumerate() {
+                for shrunk in shrink_value(item) {
+                    let mut candidate = items.clone();
+                    candidate[i] = shrunk;
+                    candidates.push(Value::Array(candidate));
+                }
+            }
+            candidates
+        }
+        Value::Object(fields) => fields
+            .iter()
+            .flat_map(|(key, field)| {
+                shrink_value(field).into_iter().map(move |shrunk| {
+                    let mut candidate = fields.clone();
+                    candidate.insert(key.clone(), shrunk);
+                    Value::Object(candidate)
+                })
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{init_simulator, to_yocto};
+    use near_sdk::json_types::U128;
+    use near_sdk::serde_json::json;
+
+    fn balance_of(user: &UserAccount, account_id: &AccountId) -> u128 {
+        let args = json!({ "account_id": account_id }).to_string();
+        let balance: U128 =
+            user.view("ft".parse().unwrap(), "ft_balance_of", args.as_bytes()).unwrap_json();
+        balance.0
+    }
+
+    fn setup() -> Vec<UserAccount> {
+        let root = init_simulator(None);
+        let ft = root.deploy(
+            include_bytes!("../../examples/fungible-token/res/fungible_token.wasm").as_ref(),
+            "ft".parse().unwrap(),
+            to_yocto("10"),
+        );
+        let args = json!({ "owner_id": root.account_id(), "total_supply": U128(1000) });
+        ft.call(ft.account_id(), "new_default_meta", args.to_string().as_bytes(), DEFAULT_GAS, 0)
+            .assert_success();
+        let mut users = vec![root];
+        for account_id in &["alice", "bob"] {
+            let user = users[0].create_user(account_id.parse().unwrap(), to_yocto("100"));
+            let args = json!({ "account_id": user.account_id() }).to_string();
+            user.call(
+                ft.account_id(),
+                "storage_deposit",
+                args.as_bytes(),
+                DEFAULT_GAS,
+                to_yocto("0.1"),
+            )
+            .assert_success();
+            users.push(user);
+        }
+        users
+    }
+
+    fn transfer(account_ids: Vec<AccountId>) -> Method {
+        Method::new("ft_transfer", move |g| {
+            json!({
+                "receiver_id": choose(g, &account_ids),
+                "amount": U128((g.next_u64() % 500) as u128),
+            })
+        })
+        .deposit(|_| 1)
+    }
+
+    #[test]
+    fn total_supply_invariant() {
+        let users = setup();
+        let account_ids: Vec<AccountId> = users.iter().map(|user| user.account_id()).collect();
+        let ids = account_ids.clone();
+        Fuzzer::new("ft".parse().unwrap(), users)
+            .method(transfer(account_ids))
+            .invariant("sum of balances == total supply", move |users| {
+                ids.iter().map(|id| balance_of(&users[0], id)).sum::<u128>() == 1000
+            })
+            .runs(3)
+            .steps(10)
+            .seed(42)
+            .run();
+    }
+
+    #[test]
+    fn shrink_violation() {
+        let users = setup();
+        let account_ids: Vec<AccountId> = users.iter().map(|user| user.account_id()).collect();
+        let root_id = account_ids[0].clone();
+        let failure = Fuzzer::new("ft".parse().unwrap(), users)
+            .method(transfer(account_ids))
+            .invariant("root keeps 900 tokens", move |users| balance_of(&users[0], &root_id) >= 900)
+            .runs(10)
+            .steps(10)
+            .seed(7)
+            .check()
+            .unwrap_err();
+        assert_eq!(failure.invariant, "root keeps 900 tokens");
+        // Only transfers from root remain.
+        assert!(failure.calls.iter().all(|call| call.caller == 0));
+        let amount =
+            |call: &FuzzCall| call.args["amount"].as_str().unwrap().parse::<u128>().unwrap();
+        let sent: u128 = failure.calls.iter().map(amount).sum();
+        // The sequence violates the invariant and is minimal: without any of its calls, root
+        // keeps enough tokens.
+        assert!(sent > 100);
+        assert!(failure.calls.iter().all(|call| sent - amount(call) <= 100));
+        assert!(failure.to_string().contains("root.ft_transfer"));
+    }
+}
@@ -9,13 +9,21 @@ pub mod outcome;
 #[doc(inline)]
 pub use outcome::*;
 mod cache;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod runtime;
+pub mod state_diff;
+pub mod state_dump;
+pub mod stepper;
+pub mod trace;
 pub mod units;
 pub mod user;
 pub use near_crypto;
 #[doc(hidden)]
 pub use near_primitives::*;
 #[doc(inline)]
+pub use stepper::*;
+#[doc(inline)]
 pub use units::*;
 #[doc(inline)]
 pub use user::*;
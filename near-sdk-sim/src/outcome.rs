@@ -1,19 +1,23 @@
 use crate::hash::CryptoHash;
-use crate::runtime::{init_runtime, RuntimeStandalone};
-use crate::transaction::{ExecutionOutcome, ExecutionStatus};
+use crate::receipt::ReceiptEnum;
+use crate::runtime::{init_runtime, GasProfile, RuntimeStandalone};
+use crate::trace::ReceiptTrace;
+use crate::transaction::{Action, ExecutionOutcome, ExecutionStatus};
 use core::fmt;
+use near_primitives::config::{ActionCosts, ExtCosts};
 use near_primitives::profile::ProfileData;
 use near_primitives::transaction::ExecutionStatus::{SuccessReceiptId, SuccessValue};
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Balance};
 use near_sdk::borsh::BorshDeserialize;
 use near_sdk::serde::de::DeserializeOwned;
 use near_sdk::serde::export::Formatter;
 use near_sdk::serde_json::Value;
 use near_sdk::Gas;
 use std::borrow::Borrow;
-use std::cell::RefCell;
 use std::fmt::Debug;
-use std::rc::Rc;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 pub type TxResult = Result<ExecutionOutcome, ExecutionOutcome>;
 
@@ -21,7 +25,7 @@ pub type TxResult = Result<ExecutionOutcome, ExecutionOutcome>;
 /// It wraps an ExecutionOutcome which is the same object returned from an RPC call.
 #[derive(Clone)]
 pub struct ExecutionResult {
-    runtime: Rc<RefCell<RuntimeStandalone>>,
+    runtime: Arc<RwLock<RuntimeStandalone>>,
     outcome: ExecutionOutcome,
     hash: CryptoHash,
 }
@@ -36,7 +40,7 @@ impl Default for ExecutionResult {
     fn default() -> Self {
         ExecutionResult::new(
             ExecutionOutcome::default(),
-            &Rc::new(RefCell::new(init_runtime(None).0)),
+            &Arc::new(RwLock::new(init_runtime(None).0)),
             CryptoHash::default(),
         )
     }
@@ -46,10 +50,10 @@ impl ExecutionResult {
     #[doc(hidden)]
     pub fn new(
         outcome: ExecutionOutcome,
-        runtime: &Rc<RefCell<RuntimeStandalone>>,
+        runtime: &Arc<RwLock<RuntimeStandalone>>,
         hash: CryptoHash,
     ) -> Self {
-        Self { runtime: Rc::clone(runtime), outcome, hash }
+        Self { runtime: Arc::clone(runtime), outcome, hash }
     }
 
     /// Interpret the SuccessValue as a JSON value
@@ -100,7 +104,7 @@ impl ExecutionResult {
     }
 
     fn get_outcome(&self, hash: &CryptoHash) -> Option<ExecutionResult> {
-        match (*self.runtime).borrow().outcome(hash) {
+        match self.runtime.read().unwrap().outcome(hash) {
             Some(out) => Some(ExecutionResult::new(out, &self.runtime, hash.clone())),
             None => None,
         }
@@ -122,7 +126,8 @@ impl ExecutionResult {
 
     /// Return the results of any promises created since the last transaction
     pub fn promise_results(&self) -> Vec<Option<ExecutionResult>> {
-        self.get_outcomes(&(*self.runtime).borrow().last_outcomes)
+        let last_outcomes = self.runtime.read().unwrap().last_outcomes.clone();
+        self.get_outcomes(&last_outcomes)
     }
 
     pub fn promise_errors(&self) -> Vec<Option<ExecutionResult>> {
@@ -169,15 +174,311 @@ impl ExecutionResult {
         &self.outcome.receipt_ids
     }
 
+    /// Asserts that the transaction which produced this outcome or one of its receipts failed with
+    /// an error containing the message, e.g. the panic message of a contract.
+    pub fn assert_failure_contains(&self, message: &str) {
+        let errors: Vec<String> = self
+            .transaction_results()
+            .iter()
+            .filter_map(|result| match &result.outcome.status {
+                ExecutionStatus::Failure(err) => Some(err.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert!(
+            errors.iter().any(|err| err.contains(message)),
+            "Expected a failure containing {:?}, got failures {:#?}",
+            message,
+            errors
+        );
+    }
+
+    /// Asserts that a log of the transaction which produced this outcome or of one of its
+    /// receipts contains the text.
+    pub fn assert_logs_contain(&self, text: &str) {
+        let logs: Vec<String> = self
+            .transaction_results()
+            .iter()
+            .flat_map(|result| result.outcome.logs.clone())
+            .collect();
+        assert!(
+            logs.iter().any(|log| log.contains(text)),
+            "Expected a log containing {:?}, got logs {:#?}",
+            text,
+            logs
+        );
+    }
+
+    /// Finds the receipt of the transaction which called the method on the account, e.g. a
+    /// callback or a call to another contract.
+    pub fn receipt_for(&self, account_id: &str, method: &str) -> Option<ExecutionResult> {
+        let results = self.transaction_results();
+        let runtime = self.runtime.read().unwrap();
+        results.into_iter().find(|result| {
+            runtime.transaction(&result.hash).is_none()
+                && result.outcome.executor_id == account_id
+                && receipt_actions(&runtime, &result.hash).iter().any(|action| {
+                    matches!(action, Action::FunctionCall(call) if call.method_name == method)
+                })
+        })
+    }
+
+    /// The amount of gas burnt by the transaction which produced this outcome and all its
+    /// receipts.
+    pub fn total_gas_burnt(&self) -> Gas {
+        self.transaction_results().iter().map(|result| result.gas_burnt()).sum()
+    }
+
+    /// Change of the balance of the account caused by the transaction which produced this outcome
+    /// and all its receipts, including deposits, burnt gas, contract rewards and refunds.
+    pub fn balance_change_of(&self, account_id: &str) -> i128 {
+        let results = self.transaction_results();
+        let runtime = self.runtime.read().unwrap();
+        results
+            .iter()
+            .map(|result| runtime.account_change_of_outcome(&result.hash, account_id).0)
+            .sum()
+    }
+
     pub fn profile_data(&self) -> ProfileData {
-        (*self.runtime).borrow().profile_of_outcome(&self.hash).unwrap()
+        ProfileData::from(&self.gas_profile())
+    }
+
+    /// Gas profile of this transaction or receipt, which is empty unless the `profile` feature is
+    /// enabled. Unlike `ProfileData`, it can be sent to other threads.
+    pub fn gas_profile(&self) -> GasProfile {
+        self.runtime.read().unwrap().profile_of_outcome(&self.hash).unwrap()
+    }
+
+    /// Gas and storage profile of this transaction or receipt and all receipts created by it.
+    /// The profile can be printed as a tree with `println!("{}", result.profile())`.
+    pub fn profile(&self) -> ReceiptProfile {
+        ReceiptProfile::new(&self.runtime.read().unwrap(), &self.hash, &self.outcome)
+    }
+
+    /// Profile of the whole transaction which produced this outcome, including the receipts
+    /// created before this outcome.
+    pub fn transaction_profile(&self) -> ReceiptProfile {
+        let runtime = self.runtime.read().unwrap();
+        let hash = self.transaction_hash(&runtime);
+        let outcome = runtime.outcome(&hash).unwrap();
+        ReceiptProfile::new(&runtime, &hash, &outcome)
+    }
+
+    /// Trace of this transaction or receipt and all receipts created by it, with their actions,
+    /// results and state changes. The trace can be printed as a tree with
+    /// `println!("{}", result.trace())`.
+    pub fn trace(&self) -> ReceiptTrace {
+        ReceiptTrace::new(&self.runtime.read().unwrap(), &self.hash, &self.outcome)
+    }
+
+    /// Trace of the whole transaction which produced this outcome.
+    pub fn transaction_trace(&self) -> ReceiptTrace {
+        let runtime = self.runtime.read().unwrap();
+        let hash = self.transaction_hash(&runtime);
+        let outcome = runtime.outcome(&hash).unwrap();
+        ReceiptTrace::new(&runtime, &hash, &outcome)
+    }
+
+    /// Writes the trace of the whole transaction which produced this outcome to a JSON file.
+    pub fn write_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.transaction_trace().to_json())
+    }
+
+    fn transaction_hash(&self, runtime: &RuntimeStandalone) -> CryptoHash {
+        let mut hash = self.hash;
+        while let Some(parent) = runtime.parent_of_outcome(&hash) {
+            hash = parent;
+        }
+        hash
+    }
+
+    /// Results of the transaction which produced this outcome and of all its receipts, in the
+    /// order of the receipt tree.
+    fn transaction_results(&self) -> Vec<ExecutionResult> {
+        let runtime = self.runtime.read().unwrap();
+        let mut hashes = vec![self.transaction_hash(&runtime)];
+        let mut results = vec![];
+        while let Some(hash) = hashes.pop() {
+            if let Some(outcome) = runtime.outcome(&hash) {
+                hashes.extend(outcome.receipt_ids.iter().rev());
+                results.push(ExecutionResult::new(outcome, &self.runtime, hash));
+            }
+        }
+        results
+    }
+}
+
+/// Actions of an executed receipt. Receipts of transactions sent to the signer are not recorded,
+/// so their actions are taken from the transaction.
+fn receipt_actions(runtime: &RuntimeStandalone, hash: &CryptoHash) -> Vec<Action> {
+    match runtime.receipt(hash) {
+        Some(receipt) => match receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => action_receipt.actions,
+            ReceiptEnum::Data(_) => vec![],
+        },
+        None => runtime
+            .parent_of_outcome(hash)
+            .and_then(|parent| runtime.transaction(&parent))
+            .map(|tx| tx.transaction.actions)
+            .unwrap_or_default(),
+    }
+}
+
+/// Gas and storage used by a transaction or receipt, with the profiles of the receipts it created.
+#[derive(Debug, Clone)]
+pub struct ReceiptProfile {
+    pub id: CryptoHash,
+    pub executor_id: AccountId,
+    /// Total gas burnt by this receipt, including the fees of the receipt and its actions.
+    pub gas_burnt: Gas,
+    pub tokens_burnt: Balance,
+    /// Gas burnt by executing wasm instructions of the contract.
+    pub wasm_gas: Gas,
+    /// Gas burnt by each host function called by the contract, e.g. `storage_write_base`.
+    pub host_functions: Vec<(&'static str, Gas)>,
+    /// Gas burnt for the actions of the promises created by the contract, e.g. `function_call`.
+    pub actions: Vec<(&'static str, Gas)>,
+    /// Change of the storage usage of the executor account, in bytes.
+    pub storage_delta: i64,
+    pub children: Vec<ReceiptProfile>,
+}
+
+impl ReceiptProfile {
+    fn new(runtime: &RuntimeStandalone, hash: &CryptoHash, outcome: &ExecutionOutcome) -> Self {
+        let profile = runtime.profile_of_outcome(hash).unwrap_or_default();
+        let host_functions: Vec<_> = (0..ExtCosts::count())
+            .map(|i| (ExtCosts::name_of(i), profile.get_ext_cost(i)))
+            .filter(|(_, gas)| *gas > 0)
+            .collect();
+        let actions: Vec<_> = (0..ActionCosts::count())
+            .map(|i| (ActionCosts::name_of(i), profile.get_action_cost(i)))
+            .filter(|(_, gas)| *gas > 0)
+            .collect();
+        Self {
+            id: *hash,
+            executor_id: outcome.executor_id.clone(),
+            gas_burnt: outcome.gas_burnt,
+            tokens_burnt: outcome.tokens_burnt,
+            wasm_gas: profile.all_gas().saturating_sub(profile.host_gas() + profile.action_gas()),
+            host_functions,
+            actions,
+            storage_delta: runtime.storage_delta_of_outcome(hash).unwrap_or_default(),
+            children: outcome
+                .receipt_ids
+                .iter()
+                .filter_map(|id| Some(Self::new(runtime, id, &runtime.outcome(id)?)))
+                .collect(),
+        }
+    }
+
+    /// Gas burnt by the host function with the given name, e.g. `storage_read_base`.
+    pub fn host_function_gas(&self, name: &str) -> Gas {
+        self.host_functions.iter().filter(|(n, _)| *n == name).map(|(_, gas)| gas).sum()
+    }
+
+    /// Gas burnt for the action with the given name, e.g. `function_call`.
+    pub fn action_gas(&self, name: &str) -> Gas {
+        self.actions.iter().filter(|(n, _)| *n == name).map(|(_, gas)| gas).sum()
+    }
+
+    /// Gas burnt by the receipt itself, excluding the wasm execution, host functions and actions.
+    pub fn fees_gas(&self) -> Gas {
+        let host_gas: Gas = self.host_functions.iter().map(|(_, gas)| gas).sum();
+        let action_gas: Gas = self.actions.iter().map(|(_, gas)| gas).sum();
+        self.gas_burnt.saturating_sub(self.wasm_gas + host_gas + action_gas)
+    }
+
+    /// Gas burnt by this receipt and all receipts created by it.
+    pub fn total_gas_burnt(&self) -> Gas {
+        self.gas_burnt + self.children.iter().map(|c| c.total_gas_burnt()).sum::<Gas>()
+    }
+
+    /// Tokens burnt by this receipt and all receipts created by it.
+    pub fn total_tokens_burnt(&self) -> Balance {
+        self.tokens_burnt + self.children.iter().map(|c| c.total_tokens_burnt()).sum::<Balance>()
+    }
+
+    /// Change of the storage usage of all accounts touched by this receipt and its descendants.
+    pub fn total_storage_delta(&self) -> i64 {
+        self.storage_delta + self.children.iter().map(|c| c.total_storage_delta()).sum::<i64>()
+    }
+
+    fn fmt_tree(
+        &self,
+        f: &mut Formatter<'_>,
+        prefix: &str,
+        child_prefix: &str,
+        total: Gas,
+    ) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{} {} ({} bytes, {} yN burnt) {}",
+            prefix,
+            self.executor_id,
+            GasBar(self.total_gas_burnt(), total),
+            self.storage_delta,
+            self.tokens_burnt,
+            self.id
+        )?;
+        let mut rows = vec![("fees".to_string(), self.fees_gas())];
+        if self.wasm_gas > 0 {
+            rows.push(("wasm".to_string(), self.wasm_gas));
+        }
+        rows.extend(self.host_functions.iter().map(|(name, gas)| (format!("host {}", name), *gas)));
+        rows.extend(self.actions.iter().map(|(name, gas)| (format!("action {}", name), *gas)));
+        let count = rows.len() + self.children.len();
+        for (i, (name, gas)) in rows.iter().enumerate() {
+            let branch = if i + 1 == count { "└── " } else { "├── " };
+            writeln!(f, "{}{}{} {}", child_prefix, branch, name, GasBar(*gas, total))?;
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let (branch, indent) = if rows.len() + i + 1 == count {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            child.fmt_tree(
+                f,
+                &format!("{}{}", child_prefix, branch),
+                &format!("{}{}", child_prefix, indent),
+                total,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the profile as a tree, where the bar of every node shows its share of the total gas.
+impl fmt::Display for ReceiptProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(f, "", "", self.total_gas_burnt())
+    }
+}
+
+struct GasBar(Gas, Gas);
+
+impl fmt::Display for GasBar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        const WIDTH: u128 = 40;
+        let GasBar(gas, total) = *self;
+        let percent = if total == 0 { 0 } else { gas as u128 * 100 / total as u128 };
+        let width = if total == 0 { 0 } else { gas as u128 * WIDTH / total as u128 };
+        write!(
+            f,
+            "{}.{:03} Tgas {}% {}",
+            gas / 10u64.pow(12),
+            gas / 10u64.pow(9) % 1000,
+            percent,
+            "█".repeat(width as usize)
+        )
     }
 }
 
 #[doc(hidden)]
 pub fn outcome_into_result(
     outcome: (CryptoHash, ExecutionOutcome),
-    runtime: &Rc<RefCell<RuntimeStandalone>>,
+    runtime: &Arc<RwLock<RuntimeStandalone>>,
 ) -> ExecutionResult {
     match (outcome.1).status {
         ExecutionStatus::SuccessValue(_) |
@@ -244,7 +545,9 @@ impl ViewResult {
 mod tests {
     use super::*;
     use crate::runtime::init_runtime;
+    use crate::{init_simulator, to_yocto, DEFAULT_GAS};
     use near_primitives::transaction::ExecutionStatus::SuccessValue;
+    use near_sdk::json_types::U128;
     use near_sdk::serde_json::json;
 
     #[test]
@@ -257,8 +560,83 @@ mod tests {
         outcome.status = status;
         let result = outcome_into_result(
             (CryptoHash::default(), outcome),
-            &Rc::new(RefCell::new(init_runtime(None).0)),
+            &Arc::new(RwLock::new(init_runtime(None).0)),
         );
         assert_eq!(value, result.unwrap_json_value());
     }
+
+    #[test]
+    fn profile_test() {
+        let root = init_simulator(None);
+        let status = root.deploy(
+            include_bytes!("../../examples/status-message/res/status_message.wasm").as_ref(),
+            "status".parse().unwrap(),
+            to_yocto("35"),
+        );
+        let res = root.call(
+            status.account_id(),
+            "set_status",
+            b"{\"message\": \"hello\"}",
+            DEFAULT_GAS,
+            0,
+        );
+        res.assert_success();
+
+        let profile = res.profile();
+        assert_eq!(profile.gas_burnt, res.gas_burnt());
+        assert!(profile.storage_delta > 0);
+        assert!(profile.actions.is_empty());
+        #[cfg(feature = "profile")]
+        {
+            assert!(profile.wasm_gas > 0);
+            assert!(profile.host_function_gas("storage_write_base") > 0);
+        }
+
+        let tx_profile = res.transaction_profile();
+        assert_eq!(tx_profile.children[0].id, profile.id);
+        assert!(tx_profile.total_gas_burnt() > profile.gas_burnt);
+        #[cfg(feature = "profile")]
+        assert!(tx_profile.to_string().contains("host storage_write_base"));
+    }
+
+    #[test]
+    fn assertion_helpers() {
+        let root = init_simulator(None);
+        let ft = root.deploy(
+            include_bytes!("../../examples/fungible-token/res/fungible_token.wasm").as_ref(),
+            "ft".parse().unwrap(),
+            to_yocto("10"),
+        );
+        let args = json!({ "owner_id": root.account_id(), "total_supply": U128(1000) });
+        ft.call(ft.account_id(), "new_default_meta", args.to_string().as_bytes(), DEFAULT_GAS, 0)
+            .assert_success();
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("100"));
+        let args = json!({ "account_id": alice.account_id() }).to_string();
+        alice
+            .call(ft.account_id(), "storage_deposit", args.as_bytes(), DEFAULT_GAS, to_yocto("0.1"))
+            .assert_success();
+
+        let args = json!({ "receiver_id": alice.account_id(), "amount": U128(10) }).to_string();
+        let res = root.call(ft.account_id(), "ft_transfer", args.as_bytes(), DEFAULT_GAS, 1);
+        res.assert_success();
+        res.assert_logs_contain("Transfer 10 from root to alice");
+        let receipt = res.receipt_for("ft", "ft_transfer").unwrap();
+        assert_eq!(receipt.executor_id(), "ft");
+        assert!(res.receipt_for("ft", "ft_balance_of").is_none());
+        assert!(res.total_gas_burnt() > receipt.gas_burnt());
+        assert_eq!(res.total_gas_burnt(), res.transaction_profile().total_gas_burnt());
+        assert!(res.balance_change_of("root") < -1);
+        assert!(res.balance_change_of("ft") > 1);
+        assert_eq!(res.balance_change_of("alice"), 0);
+
+        let args = json!({ "receiver_id": alice.account_id(), "amount": U128(2000) }).to_string();
+        let res = root.call(ft.account_id(), "ft_transfer", args.as_bytes(), DEFAULT_GAS, 1);
+        res.assert_failure_contains("The account doesn't have enough balance");
+
+        let res = root.transfer(alice.account_id(), to_yocto("10"));
+        assert_eq!(res.balance_change_of("alice"), to_yocto("10") as i128);
+        let root_change = res.balance_change_of("root");
+        assert!(root_change < -(to_yocto("10") as i128));
+        assert!(root_change > -(to_yocto("10.01") as i128));
+    }
 }
@@ -1,31 +1,44 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::sync::Arc;
 
 use crate::cache::{cache_to_arc, create_cache, ContractCache};
+use crate::state_diff::StateDiff;
 use crate::ViewResult;
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signer};
 use near_pool::{types::PoolIterator, TransactionPool};
 use near_primitives::account::{AccessKey, Account};
-use near_primitives::errors::RuntimeError;
-use near_primitives::hash::CryptoHash;
+use near_primitives::borsh::BorshDeserialize;
+use near_primitives::config::{ActionCosts, ExtCosts};
+use near_primitives::errors::{ActionError, ActionErrorKind, RuntimeError};
+use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::profile::ProfileData;
-use near_primitives::receipt::Receipt;
+use near_primitives::receipt::{DataReceipt, Receipt, ReceiptEnum};
 use near_primitives::runtime::config::RuntimeConfig;
 use near_primitives::state_record::StateRecord;
 use near_primitives::test_utils::account_new;
 use near_primitives::test_utils::MockEpochInfoProvider;
-use near_primitives::transaction::{ExecutionOutcome, ExecutionStatus, SignedTransaction};
+use near_primitives::transaction::{Action, ExecutionOutcome, ExecutionStatus, SignedTransaction};
+use near_primitives::trie_key::{trie_key_parsers::get_raw_prefix_for_contract_data, TrieKey};
 use near_primitives::types::{
-    AccountInfo, Balance, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, Gas,
-    StateChangeCause,
+    AccountInfo, Balance, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, Gas, NumShards,
+    RawStateChangesWithTrieKey, ShardId, StateChangeCause,
 };
+use near_primitives::utils::create_receipt_id_from_receipt;
 use near_primitives::version::PROTOCOL_VERSION;
 use near_primitives::views::ViewApplyState;
-use near_runtime::{state_viewer::TrieViewer, ApplyState, Runtime};
+use near_runtime::config::{
+    safe_add_gas, safe_gas_to_balance, total_deposit, total_exec_fees, total_prepaid_gas,
+};
+use near_runtime::{state_viewer::TrieViewer, verify_and_charge_transaction, ApplyState, Runtime};
 use near_sdk::{AccountId, Duration};
+#[cfg(feature = "profile")]
+use near_store::DBCol;
 use near_store::{
     get_access_key, get_account, set_account, test_utils::create_test_store, ShardTries, Store,
+    StoreUpdate, TrieChanges,
 };
+use near_vm_errors::{FunctionCallError, HostError};
 
 const DEFAULT_EPOCH_LENGTH: u64 = 3;
 const DEFAULT_BLOCK_PROD_TIME: Duration = 1_000_000_000;
@@ -41,7 +54,7 @@ pub fn init_runtime(
     (runtime, signer, root_account_id)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GenesisConfig {
     pub genesis_time: u64,
     pub gas_price: Balance,
@@ -52,6 +65,17 @@ pub struct GenesisConfig {
     pub runtime_config: RuntimeConfig,
     pub state_records: Vec<StateRecord>,
     pub validators: Vec<AccountInfo>,
+    /// Random seed of each block, returned by `env::random_seed`.
+    pub random_seed: HashSource,
+    /// Hash of each block, used to derive the IDs of receipts.
+    pub block_hash: HashSource,
+    /// Number of shards which accounts are assigned to. The state of all shards is stored in one
+    /// trie, shards only affect the order and the delivery time of receipts.
+    pub num_shards: NumShards,
+    pub shard_assignment: ShardAssignment,
+    /// Number of blocks by which receipts sent to another shard arrive later than receipts sent
+    /// within a shard, e.g. because of missing chunks.
+    pub cross_shard_delay: BlockHeight,
 }
 
 impl Default for GenesisConfig {
@@ -72,6 +96,11 @@ impl Default for GenesisConfig {
             runtime_config,
             state_records: vec![],
             validators: vec![],
+            random_seed: HashSource::Zero,
+            block_hash: HashSource::Zero,
+            num_shards: 1,
+            shard_assignment: ShardAssignment::Hash,
+            cross_shard_delay: 0,
         }
     }
 }
@@ -94,6 +123,111 @@ impl GenesisConfig {
     }
 }
 
+/// Produces a hash for every block height, e.g. the random seed of the block.
+#[derive(Clone)]
+pub enum HashSource {
+    /// The default hash for every block.
+    Zero,
+    /// Hashes taken in order from the sequence, starting again from the first one once the
+    /// sequence is exhausted. The hash of block `h` is `sequence[h % sequence.len()]`, or the
+    /// default hash if the sequence is empty.
+    Sequence(Vec<CryptoHash>),
+    /// Different hash for each block, derived from the SHA-256 of the seed and the block height.
+    FromSeed(Vec<u8>),
+    /// Hash returned by the function for the block height.
+    Custom(Arc<dyn Fn(BlockHeight) -> CryptoHash + Send + Sync>),
+}
+
+impl HashSource {
+    pub fn hash_at(&self, block_height: BlockHeight) -> CryptoHash {
+        match self {
+            HashSource::Zero => CryptoHash::default(),
+            HashSource::Sequence(sequence) if sequence.is_empty() => CryptoHash::default(),
+            HashSource::Sequence(sequence) => {
+                sequence[(block_height % sequence.len() as u64) as usize]
+            }
+            HashSource::FromSeed(seed) => {
+                let mut data = seed.clone();
+                data.extend_from_slice(&block_height.to_le_bytes());
+                hash(&data)
+            }
+            HashSource::Custom(f) => f(block_height),
+        }
+    }
+}
+
+impl Default for HashSource {
+    fn default() -> Self {
+        HashSource::Zero
+    }
+}
+
+impl fmt::Debug for HashSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashSource::Zero => write!(f, "Zero"),
+            HashSource::Sequence(sequence) => f.debug_tuple("Sequence").field(sequence).finish(),
+            HashSource::FromSeed(seed) => f.debug_tuple("FromSeed").field(seed).finish(),
+            HashSource::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// Assigns accounts to shards.
+#[derive(Clone)]
+pub enum ShardAssignment {
+    /// Shard derived from the SHA-256 of the account ID, the same way as by nodes with a fixed
+    /// number of shards.
+    Hash,
+    /// Shards of the listed accounts. Other accounts are assigned by hash.
+    Accounts(HashMap<String, ShardId>),
+    /// Shard returned by the function for the account ID.
+    Custom(Arc<dyn Fn(&str) -> ShardId + Send + Sync>),
+}
+
+impl ShardAssignment {
+    pub fn shard_of(&self, account_id: &str, num_shards: NumShards) -> ShardId {
+        let shard_id = match self {
+            ShardAssignment::Hash => hash_shard_of(account_id, num_shards),
+            ShardAssignment::Accounts(shards) => match shards.get(account_id) {
+                Some(shard_id) => *shard_id,
+                None => hash_shard_of(account_id, num_shards),
+            },
+            ShardAssignment::Custom(f) => f(account_id),
+        };
+        assert!(
+            shard_id < num_shards,
+            "Account {} is assigned to shard {}, but there are {} shards",
+            account_id,
+            shard_id,
+            num_shards
+        );
+        shard_id
+    }
+}
+
+fn hash_shard_of(account_id: &str, num_shards: NumShards) -> ShardId {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&(hash(account_id.as_bytes()).0).0[..8]);
+    u64::from_le_bytes(bytes) % num_shards
+}
+
+impl Default for ShardAssignment {
+    fn default() -> Self {
+        ShardAssignment::Hash
+    }
+}
+
+impl fmt::Debug for ShardAssignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShardAssignment::Hash => write!(f, "Hash"),
+            ShardAssignment::Accounts(shards) => f.debug_tuple("Accounts").field(shards).finish(),
+            ShardAssignment::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Block {
     prev_block: Option<Arc<Block>>,
@@ -131,6 +265,13 @@ impl Block {
         }
     }
 
+    /// Root of the state after this block, which can be passed to
+    /// [`RuntimeStandalone::state_diff`]. The state is removed from the store when the next block
+    /// is produced, unless a [`Snapshot`] or a fork of the runtime is alive.
+    pub fn state_root(&self) -> CryptoHash {
+        self.state_root
+    }
+
     fn produce(
         &self,
         new_state_root: CryptoHash,
@@ -150,19 +291,233 @@ impl Block {
     }
 }
 
+/// State of the runtime at some block, which can be restored with [`RuntimeStandalone::restore`].
+/// Besides the block, it holds the transactions which are not included into a block yet, the
+/// pending receipts, the validators and everything recorded about executed transactions and
+/// receipts.
+#[derive(Clone)]
+pub struct Snapshot {
+    block: Block,
+    pending_transactions: Vec<SignedTransaction>,
+    transactions: HashMap<CryptoHash, SignedTransaction>,
+    outcomes: HashMap<CryptoHash, ExecutionOutcome>,
+    profile: HashMap<CryptoHash, GasProfile>,
+    account_changes: HashMap<CryptoHash, HashMap<String, (i128, i64)>>,
+    receipts: HashMap<CryptoHash, Receipt>,
+    state_changes: HashMap<CryptoHash, Vec<(TrieKey, Option<Vec<u8>>)>>,
+    state_roots: HashMap<CryptoHash, (CryptoHash, CryptoHash)>,
+    parents: HashMap<CryptoHash, CryptoHash>,
+    pending_receipts: Vec<Receipt>,
+    in_flight_receipts: Vec<(BlockHeight, Receipt)>,
+    failing_receipts: HashSet<CryptoHash>,
+    validators: Vec<AccountInfo>,
+    last_outcomes: Vec<CryptoHash>,
+    _guard: Arc<()>,
+}
+
+impl Snapshot {
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+}
+
+/// Gas burnt by a receipt, split into host functions and actions. It holds a copy of the
+/// `ProfileData` of the runtime, which can't be sent to other threads because it shares its
+/// counters through an `Rc`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct GasProfile {
+    all_gas: Gas,
+    ext_costs: Vec<Gas>,
+    action_costs: Vec<Gas>,
+}
+
+impl GasProfile {
+    pub fn all_gas(&self) -> Gas {
+        self.all_gas
+    }
+
+    pub fn get_ext_cost(&self, ext: usize) -> Gas {
+        self.ext_costs.get(ext).copied().unwrap_or_default()
+    }
+
+    pub fn get_action_cost(&self, action: usize) -> Gas {
+        self.action_costs.get(action).copied().unwrap_or_default()
+    }
+
+    pub fn host_gas(&self) -> Gas {
+        self.ext_costs.iter().sum()
+    }
+
+    pub fn action_gas(&self) -> Gas {
+        self.action_costs.iter().sum()
+    }
+
+    pub fn wasm_gas(&self) -> Gas {
+        self.all_gas.saturating_sub(self.host_gas() + self.action_gas())
+    }
+}
+
+impl From<&ProfileData> for GasProfile {
+    fn from(profile: &ProfileData) -> Self {
+        Self {
+            all_gas: profile.all_gas(),
+            ext_costs: (0..ExtCosts::count()).map(|i| profile.get_ext_cost(i)).collect(),
+            action_costs: (0..ActionCosts::count()).map(|i| profile.get_action_cost(i)).collect(),
+        }
+    }
+}
+
+/// The costs in the order of their index in `ProfileData`. Costs of protocol features are left out.
+const EXT_COSTS: &[ExtCosts] = {
+    use ExtCosts::*;
+    &[
+        base,
+        contract_compile_base,
+        contract_compile_bytes,
+        read_memory_base,
+        read_memory_byte,
+        write_memory_base,
+        write_memory_byte,
+        read_register_base,
+        read_register_byte,
+        write_register_base,
+        write_register_byte,
+        utf8_decoding_base,
+        utf8_decoding_byte,
+        utf16_decoding_base,
+        utf16_decoding_byte,
+        sha256_base,
+        sha256_byte,
+        keccak256_base,
+        keccak256_byte,
+        keccak512_base,
+        keccak512_byte,
+        log_base,
+        log_byte,
+        storage_write_base,
+        storage_write_key_byte,
+        storage_write_value_byte,
+        storage_write_evicted_byte,
+        storage_read_base,
+        storage_read_key_byte,
+        storage_read_value_byte,
+        storage_remove_base,
+        storage_remove_key_byte,
+        storage_remove_ret_value_byte,
+        storage_has_key_base,
+        storage_has_key_byte,
+        storage_iter_create_prefix_base,
+        storage_iter_create_prefix_byte,
+        storage_iter_create_range_base,
+        storage_iter_create_from_byte,
+        storage_iter_create_to_byte,
+        storage_iter_next_base,
+        storage_iter_next_key_byte,
+        storage_iter_next_value_byte,
+        touching_trie_node,
+        promise_and_base,
+        promise_and_per_promise,
+        promise_return,
+        validator_stake_base,
+        validator_total_stake_base,
+    ]
+};
+
+const ACTION_COSTS: &[ActionCosts] = {
+    use ActionCosts::*;
+    &[
+        create_account,
+        delete_account,
+        deploy_contract,
+        function_call,
+        transfer,
+        stake,
+        add_key,
+        delete_key,
+        value_return,
+        new_receipt,
+    ]
+};
+
+/// Creates a new `ProfileData` with the costs of the profile. It is empty unless the cost
+/// counters of the runtime are enabled with the `profile` feature.
+impl From<&GasProfile> for ProfileData {
+    fn from(profile: &GasProfile) -> Self {
+        let profile_data = ProfileData::new_enabled();
+        profile_data.set_burnt_gas(profile.all_gas);
+        for ext in EXT_COSTS {
+            profile_data.add_ext_cost(*ext, profile.get_ext_cost(*ext as usize));
+        }
+        for action in ACTION_COSTS {
+            profile_data.add_action_cost(*action, profile.get_action_cost(*action as usize));
+        }
+        profile_data
+    }
+}
+
+/// Same format as the `Debug` output of `ProfileData`.
+impl fmt::Debug for GasProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |gas: Gas, total: Gas| if total == 0 { 0 } else { gas * 100 / total };
+        let (all_gas, host_gas) = (self.all_gas, self.host_gas());
+        writeln!(f, "------------------------------")?;
+        writeln!(f, "Total gas: {}", all_gas)?;
+        writeln!(f, "Host gas: {} [{}% total]", host_gas, percent(host_gas, all_gas))?;
+        let action_gas = self.action_gas();
+        writeln!(f, "Action gas: {} [{}% total]", action_gas, percent(action_gas, all_gas))?;
+        let wasm_gas = self.wasm_gas();
+        writeln!(f, "Wasm execution: {} [{}% total]", wasm_gas, percent(wasm_gas, all_gas))?;
+        writeln!(f, "------ Host functions --------")?;
+        for (i, gas) in self.ext_costs.iter().enumerate().filter(|(_, gas)| **gas != 0) {
+            writeln!(
+                f,
+                "{} -> {} [{}% total, {}% host]",
+                ExtCosts::name_of(i),
+                gas,
+                percent(*gas, all_gas),
+                percent(*gas, host_gas)
+            )?;
+        }
+        writeln!(f, "------ Actions --------")?;
+        for (i, gas) in self.action_costs.iter().enumerate().filter(|(_, gas)| **gas != 0) {
+            writeln!(
+                f,
+                "{} -> {} [{}% total]",
+                ActionCosts::name_of(i),
+                gas,
+                percent(*gas, all_gas)
+            )?;
+        }
+        writeln!(f, "------------------------------")
+    }
+}
+
 pub struct RuntimeStandalone {
     pub genesis: GenesisConfig,
     tx_pool: TransactionPool,
     transactions: HashMap<CryptoHash, SignedTransaction>,
     outcomes: HashMap<CryptoHash, ExecutionOutcome>,
-    profile: HashMap<CryptoHash, ProfileData>,
+    profile: HashMap<CryptoHash, GasProfile>,
+    /// Change of the balance and of the storage usage of each account changed by a transaction or
+    /// receipt.
+    account_changes: HashMap<CryptoHash, HashMap<String, (i128, i64)>>,
+    receipts: HashMap<CryptoHash, Receipt>,
+    state_changes: HashMap<CryptoHash, Vec<(TrieKey, Option<Vec<u8>>)>>,
+    state_roots: HashMap<CryptoHash, (CryptoHash, CryptoHash)>,
+    parents: HashMap<CryptoHash, CryptoHash>,
     pub cur_block: Block,
     runtime: Runtime,
     tries: ShardTries,
     pending_receipts: Vec<Receipt>,
-    epoch_info_provider: Box<dyn EpochInfoProvider>,
+    in_flight_receipts: Vec<(BlockHeight, Receipt)>,
+    failing_receipts: HashSet<CryptoHash>,
+    validators: Vec<AccountInfo>,
+    epoch_info_provider: Box<dyn EpochInfoProvider + Send + Sync>,
     pub last_outcomes: Vec<CryptoHash>,
     cache: ContractCache,
+    /// Shared with every snapshot and fork of the runtime. While any of them is alive, the state
+    /// of previous blocks is kept in the store.
+    snapshots: Arc<()>,
 }
 
 impl RuntimeStandalone {
@@ -182,6 +537,7 @@ impl RuntimeStandalone {
         store_update.commit().unwrap();
         genesis_block.state_root = state_root;
         let validators = genesis.validators.clone();
+        let epoch_info_provider = Self::epoch_info_provider(&validators);
         Self {
             genesis,
             tries,
@@ -189,14 +545,21 @@ impl RuntimeStandalone {
             transactions: HashMap::new(),
             outcomes: HashMap::new(),
             profile: HashMap::new(),
+            account_changes: HashMap::new(),
+            receipts: HashMap::new(),
+            state_changes: HashMap::new(),
+            state_roots: HashMap::new(),
+            parents: HashMap::new(),
             cur_block: genesis_block,
             tx_pool: TransactionPool::new(),
             pending_receipts: vec![],
-            epoch_info_provider: Box::new(MockEpochInfoProvider::new(
-                validators.into_iter().map(|info| (info.account_id, info.amount)),
-            )),
+            in_flight_receipts: vec![],
+            failing_receipts: HashSet::new(),
+            validators,
+            epoch_info_provider,
             cache: create_cache(),
             last_outcomes: vec![],
+            snapshots: Arc::new(()),
         }
     }
 
@@ -204,6 +567,117 @@ impl RuntimeStandalone {
         RuntimeStandalone::new(genesis, create_test_store())
     }
 
+    fn epoch_info_provider(validators: &[AccountInfo]) -> Box<dyn EpochInfoProvider + Send + Sync> {
+        Box::new(MockEpochInfoProvider::new(
+            validators.iter().map(|info| (info.account_id.clone(), info.amount)),
+        ))
+    }
+
+    /// Replaces the validators and their stakes, e.g. to simulate a new epoch. The change takes
+    /// effect in the next block, and is visible through `env::validator_stake`.
+    pub fn set_validators(&mut self, validators: Vec<AccountInfo>) {
+        self.epoch_info_provider = Self::epoch_info_provider(&validators);
+        self.validators = validators;
+    }
+
+    /// The current validators.
+    pub fn validators(&self) -> &[AccountInfo] {
+        &self.validators
+    }
+
+    /// Captures the current state, so that it can be restored later. Only the records of the
+    /// runtime are copied: while the snapshot is alive, the state of previous blocks is kept in the
+    /// store instead of being removed when a new block is produced.
+    ///
+    /// # Examples
+    /// ```
+    /// use near_sdk_sim::runtime::init_runtime;
+    /// let (mut runtime, _, _) = init_runtime(None);
+    /// let snapshot = runtime.snapshot();
+    /// runtime.produce_blocks(5).unwrap();
+    /// runtime.restore(&snapshot);
+    /// assert_eq!(runtime.current_block().block_height, 0);
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        // Every transaction included into a block gets an outcome, the rest are still in the pool.
+        let pending_transactions = self
+            .transactions
+            .iter()
+            .filter(|(hash, _)| !self.outcomes.contains_key(hash))
+            .map(|(_, tx)| tx.clone())
+            .collect();
+        Snapshot {
+            block: self.cur_block.clone(),
+            pending_transactions,
+            transactions: self.transactions.clone(),
+            outcomes: self.outcomes.clone(),
+            profile: self.profile.clone(),
+            account_changes: self.account_changes.clone(),
+            receipts: self.receipts.clone(),
+            state_changes: self.state_changes.clone(),
+            state_roots: self.state_roots.clone(),
+            parents: self.parents.clone(),
+            pending_receipts: self.pending_receipts.clone(),
+            in_flight_receipts: self.in_flight_receipts.clone(),
+            failing_receipts: self.failing_receipts.clone(),
+            validators: self.validators.clone(),
+            last_outcomes: self.last_outcomes.clone(),
+            _guard: self.snapshots.clone(),
+        }
+    }
+
+    /// Rolls the runtime back to the snapshot. Transactions sent and outcomes recorded after the
+    /// snapshot are forgotten.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.cur_block = snapshot.block.clone();
+        self.tx_pool = TransactionPool::new();
+        for tx in snapshot.pending_transactions.iter() {
+            self.tx_pool.insert_transaction(tx.clone());
+        }
+        self.transactions = snapshot.transactions.clone();
+        self.outcomes = snapshot.outcomes.clone();
+        self.profile = snapshot.profile.clone();
+        self.account_changes = snapshot.account_changes.clone();
+        self.receipts = snapshot.receipts.clone();
+        self.state_changes = snapshot.state_changes.clone();
+        self.state_roots = snapshot.state_roots.clone();
+        self.parents = snapshot.parents.clone();
+        self.pending_receipts = snapshot.pending_receipts.clone();
+        self.in_flight_receipts = snapshot.in_flight_receipts.clone();
+        self.failing_receipts = snapshot.failing_receipts.clone();
+        self.set_validators(snapshot.validators.clone());
+        self.last_outcomes = snapshot.last_outcomes.clone();
+    }
+
+    /// Creates an independent copy of the runtime. Both runtimes share the underlying store, but
+    /// changes made in one of them are not visible in the other one. The state of previous blocks
+    /// is kept in the store as long as both runtimes are alive.
+    pub fn fork(&self) -> Self {
+        Self {
+            genesis: self.genesis.clone(),
+            tx_pool: TransactionPool::new(),
+            transactions: self.transactions.clone(),
+            outcomes: self.outcomes.clone(),
+            profile: self.profile.clone(),
+            account_changes: self.account_changes.clone(),
+            receipts: self.receipts.clone(),
+            state_changes: self.state_changes.clone(),
+            state_roots: self.state_roots.clone(),
+            parents: self.parents.clone(),
+            cur_block: self.cur_block.clone(),
+            runtime: Runtime::new(),
+            tries: self.tries.clone(),
+            pending_receipts: self.pending_receipts.clone(),
+            in_flight_receipts: self.in_flight_receipts.clone(),
+            failing_receipts: self.failing_receipts.clone(),
+            validators: self.validators.clone(),
+            epoch_info_provider: Self::epoch_info_provider(&self.validators),
+            last_outcomes: self.last_outcomes.clone(),
+            cache: self.cache.clone(),
+            snapshots: self.snapshots.clone(),
+        }
+    }
+
     /// Processes blocks until the final value is produced
     pub fn resolve_tx(
         &mut self,
@@ -224,7 +698,7 @@ impl RuntimeStandalone {
                         return Ok((outcome_hash, outcome.clone()))
                     }
                 };
-            } else if self.pending_receipts.is_empty() {
+            } else if !self.has_pending_receipts() {
                 unreachable!("Lost an outcome for the receipt hash {}", outcome_hash);
             }
         }
@@ -242,18 +716,65 @@ impl RuntimeStandalone {
         self.outcomes.get(hash).cloned()
     }
 
-    pub fn profile_of_outcome(&self, hash: &CryptoHash) -> Option<ProfileData> {
-        match self.profile.get(hash) {
-            Some(p) => Some(p.clone()),
-            _ => None,
-        }
+    /// Returns a transaction sent to this runtime.
+    pub fn transaction(&self, hash: &CryptoHash) -> Option<SignedTransaction> {
+        self.transactions.get(hash).cloned()
+    }
+
+    /// Returns an executed receipt. Receipts which the runtime executes in the same block as their
+    /// transaction, i.e. transactions sent to the signer account, are not recorded.
+    pub fn receipt(&self, receipt_id: &CryptoHash) -> Option<Receipt> {
+        self.receipts.get(receipt_id).cloned()
+    }
+
+    /// Returns the final value of every key changed by the given transaction or receipt. The value
+    /// is `None` if the key was removed.
+    pub fn state_changes_of_outcome(&self, hash: &CryptoHash) -> Vec<(TrieKey, Option<Vec<u8>>)> {
+        self.state_changes.get(hash).cloned().unwrap_or_default()
+    }
+
+    /// Returns the state roots before and after the block in which the given transaction or
+    /// receipt was applied. See [`Block::state_root`] for how long their state is kept.
+    pub fn state_roots_of_outcome(&self, hash: &CryptoHash) -> Option<(CryptoHash, CryptoHash)> {
+        self.state_roots.get(hash).cloned()
+    }
+
+    /// Gas profile of the given transaction or receipt. The profile is empty unless the `profile`
+    /// feature is enabled, which enables the cost counters of the runtime.
+    pub fn profile_of_outcome(&self, hash: &CryptoHash) -> Option<GasProfile> {
+        self.profile.get(hash).cloned()
+    }
+
+    /// Change of the storage usage of the executor account caused by the given receipt, in bytes.
+    pub fn storage_delta_of_outcome(&self, hash: &CryptoHash) -> Option<i64> {
+        let outcome = self.outcomes.get(hash)?;
+        Some(self.account_change_of_outcome(hash, &outcome.executor_id).1)
+    }
+
+    /// Change of the balance and of the storage usage of the account caused by the given
+    /// transaction or receipt.
+    pub(crate) fn account_change_of_outcome(
+        &self,
+        hash: &CryptoHash,
+        account_id: &str,
+    ) -> (i128, i64) {
+        self.account_changes
+            .get(hash)
+            .and_then(|changes| changes.get(account_id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the transaction or receipt which created the given receipt.
+    pub fn parent_of_outcome(&self, hash: &CryptoHash) -> Option<CryptoHash> {
+        self.parents.get(hash).cloned()
     }
 
     /// Processes all transactions and pending receipts until there is no pending_receipts left
     pub fn process_all(&mut self) -> Result<(), RuntimeError> {
         loop {
             self.produce_block()?;
-            if self.pending_receipts.is_empty() {
+            if !self.has_pending_receipts() {
                 return Ok(());
             }
         }
@@ -261,16 +782,102 @@ impl RuntimeStandalone {
 
     /// Processes one block. Populates outcomes and producining new pending_receipts.
     pub fn produce_block(&mut self) -> Result<(), RuntimeError> {
-        let profile_data = ProfileData::default();
-        let apply_state = ApplyState {
-            block_index: self.cur_block.block_height,
-            prev_block_hash: Default::default(),
+        let mut transactions = Self::prepare_transactions(&mut self.tx_pool);
+        let mut receipts = std::mem::take(&mut self.pending_receipts);
+        // The whole block is applied as one chunk, in which the transactions and the receipts of
+        // each shard come after the ones of the previous shards.
+        transactions.sort_by_key(|tx| self.shard_of(&tx.transaction.signer_id));
+        receipts.sort_by_key(|receipt| self.shard_of(&receipt.receiver_id));
+        let transactions = self.reject_invalid_transactions(transactions)?;
+        for receipt in receipts.iter() {
+            self.receipts.insert(receipt.receipt_id, receipt.clone());
+        }
+        let failing_receipts = &mut self.failing_receipts;
+        let (failing_receipts, receipts): (Vec<_>, Vec<_>) =
+            receipts.into_iter().partition(|receipt| failing_receipts.remove(&receipt.receipt_id));
+        #[cfg(feature = "profile")]
+        self.record_profiles(&receipts, &transactions)?;
+        let mut outgoing_receipts = self.apply(&receipts, &transactions)?;
+        for receipt in failing_receipts {
+            outgoing_receipts.extend(self.fail_receipt(&receipt));
+        }
+        self.cur_block = self.cur_block.produce(
+            self.cur_block.state_root,
+            self.genesis.epoch_length,
+            self.genesis.block_prod_time,
+        );
+        for receipt in outgoing_receipts {
+            // A receipt is sent by the shard of the transaction or receipt which created it.
+            let sender_id = match self.parents.get(&receipt.receipt_id) {
+                Some(parent) => &self.outcomes[parent].executor_id,
+                None => &receipt.predecessor_id,
+            };
+            let delay = if self.shard_of(&receipt.receiver_id) == self.shard_of(sender_id) {
+                0
+            } else {
+                self.genesis.cross_shard_delay
+            };
+            self.in_flight_receipts.push((self.cur_block.block_height + delay, receipt));
+        }
+        self.deliver_receipts();
+
+        Ok(())
+    }
+
+    /// Nodes reject invalid transactions, e.g. signed with a key which is not allowed to call the
+    /// method, instead of including them into a chunk. The error is reported as the outcome of
+    /// the transaction. Returns the valid transactions.
+    fn reject_invalid_transactions(
+        &mut self,
+        transactions: Vec<SignedTransaction>,
+    ) -> Result<Vec<SignedTransaction>, RuntimeError> {
+        // The transactions are charged in order, e.g. to check that nonces increase.
+        let mut state_update = self.tries.new_trie_update(0, self.cur_block.state_root);
+        let mut valid_transactions = vec![];
+        for transaction in transactions {
+            let hash = transaction.get_hash();
+            match verify_and_charge_transaction(
+                &self.genesis.runtime_config,
+                &mut state_update,
+                self.cur_block.gas_price,
+                &transaction,
+                true,
+                PROTOCOL_VERSION,
+            ) {
+                Ok(_) => {
+                    state_update.commit(StateChangeCause::TransactionProcessing { tx_hash: hash });
+                    valid_transactions.push(transaction);
+                }
+                Err(RuntimeError::InvalidTxError(err)) => {
+                    state_update.rollback();
+                    let outcome = ExecutionOutcome {
+                        status: ExecutionStatus::Failure(err.into()),
+                        executor_id: transaction.transaction.signer_id.clone(),
+                        ..Default::default()
+                    };
+                    self.last_outcomes.push(hash);
+                    self.outcomes.insert(hash, outcome);
+                    self.profile.insert(hash, GasProfile::default());
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(valid_transactions)
+    }
+
+    fn apply_state(&self, profile: ProfileData) -> ApplyState {
+        let block_height = self.cur_block.block_height;
+        ApplyState {
+            block_index: block_height,
+            prev_block_hash: match block_height.checked_sub(1) {
+                Some(prev_height) => self.genesis.block_hash.hash_at(prev_height),
+                None => CryptoHash::default(),
+            },
             epoch_height: self.cur_block.epoch_height,
             gas_price: self.cur_block.gas_price,
             block_timestamp: self.cur_block.block_timestamp,
             gas_limit: None,
-            // not used
-            random_seed: Default::default(),
+            random_seed: self.genesis.random_seed.hash_at(block_height),
             epoch_id: EpochId::default(),
             current_protocol_version: PROTOCOL_VERSION,
             config: Arc::new(self.genesis.runtime_config.clone()),
@@ -278,37 +885,269 @@ impl RuntimeStandalone {
             cache: None,
             #[cfg(not(feature = "no_contract_cache"))]
             cache: Some(cache_to_arc(&self.cache)),
-            profile: profile_data.clone(),
-            block_hash: Default::default(),
-        };
+            profile,
+            block_hash: self.genesis.block_hash.hash_at(block_height),
+        }
+    }
 
+    fn apply(
+        &mut self,
+        receipts: &[Receipt],
+        transactions: &[SignedTransaction],
+    ) -> Result<Vec<Receipt>, RuntimeError> {
+        let prev_state_root = self.cur_block.state_root;
         let apply_result = self.runtime.apply(
             self.tries.get_trie_for_shard(0),
-            self.cur_block.state_root,
+            prev_state_root,
             &None,
-            &apply_state,
-            &self.pending_receipts,
-            &Self::prepare_transactions(&mut self.tx_pool),
+            &self.apply_state(ProfileData::default()),
+            receipts,
+            transactions,
             self.epoch_info_provider.as_ref(),
         )?;
-        self.pending_receipts = apply_result.outgoing_receipts;
-        apply_result.outcomes.iter().for_each(|outcome| {
+        self.record_account_changes(prev_state_root, &apply_result.state_changes);
+        self.commit_trie_changes(&apply_result.trie_changes);
+        self.cur_block.state_root = apply_result.state_root;
+        self.record_state_changes(apply_result.state_changes);
+
+        for outcome in apply_result.outcomes {
+            self.state_roots.insert(outcome.id, (prev_state_root, apply_result.state_root));
+            for receipt_id in outcome.outcome.receipt_ids.iter() {
+                self.parents.insert(*receipt_id, outcome.id);
+            }
             self.last_outcomes.push(outcome.id);
-            self.outcomes.insert(outcome.id, outcome.outcome.clone());
-            self.profile.insert(outcome.id, profile_data.clone());
-        });
-        let (update, _) =
-            self.tries.apply_all(&apply_result.trie_changes, 0).expect("Unexpected Storage error");
-        update.commit().expect("Unexpected io error");
-        self.cur_block = self.cur_block.produce(
-            apply_result.state_root,
-            self.genesis.epoch_length,
-            self.genesis.block_prod_time,
-        );
+            self.outcomes.insert(outcome.id, outcome.outcome);
+            self.profile.entry(outcome.id).or_default();
+        }
+        Ok(apply_result.outgoing_receipts)
+    }
+
+    /// Fails an action receipt without executing it, see [`RuntimeStandalone::inject_failure`].
+    /// Returns the refunds and the data receipts with the failed result, like the runtime does
+    /// for a receipt whose action failed.
+    fn fail_receipt(&mut self, receipt: &Receipt) -> Vec<Receipt> {
+        let action_receipt = match &receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => action_receipt,
+            ReceiptEnum::Data(_) => unreachable!("Only action receipts can fail"),
+        };
+        let fees = &self.genesis.runtime_config.transaction_costs;
+        let actions = &action_receipt.actions;
+        let deposit = total_deposit(actions).expect("Integer overflow");
+        // The receipt burns no gas, so both the prepaid gas and the execution fees are refunded.
+        let gas = safe_add_gas(
+            total_prepaid_gas(actions).expect("Integer overflow"),
+            total_exec_fees(fees, actions, &receipt.receiver_id, PROTOCOL_VERSION)
+                .expect("Integer overflow"),
+        )
+        .and_then(|gas| safe_add_gas(gas, fees.action_receipt_creation_config.exec_fee()))
+        .expect("Integer overflow");
+        let gas_refund =
+            safe_gas_to_balance(action_receipt.gas_price, gas).expect("Integer overflow");
+
+        let mut new_receipts = vec![];
+        if deposit > 0 {
+            new_receipts.push(Receipt::new_balance_refund(&receipt.predecessor_id, deposit));
+        }
+        if gas_refund > 0 {
+            new_receipts.push(Receipt::new_gas_refund(
+                &action_receipt.signer_id,
+                gas_refund,
+                action_receipt.signer_public_key.clone(),
+            ));
+        }
+        new_receipts.extend(action_receipt.output_data_receivers.iter().map(|data_receiver| {
+            Receipt {
+                predecessor_id: receipt.receiver_id.clone(),
+                receiver_id: data_receiver.receiver_id.clone(),
+                receipt_id: CryptoHash::default(),
+                receipt: ReceiptEnum::Data(DataReceipt {
+                    data_id: data_receiver.data_id,
+                    data: None,
+                }),
+            }
+        }));
+        let apply_state = self.apply_state(ProfileData::default());
+        let mut receipt_ids = vec![];
+        for (receipt_index, new_receipt) in new_receipts.iter_mut().enumerate() {
+            new_receipt.receipt_id = create_receipt_id_from_receipt(
+                PROTOCOL_VERSION,
+                receipt,
+                &apply_state.prev_block_hash,
+                &apply_state.block_hash,
+                receipt_index,
+            );
+            if let ReceiptEnum::Action(_) = new_receipt.receipt {
+                self.parents.insert(new_receipt.receipt_id, receipt.receipt_id);
+                receipt_ids.push(new_receipt.receipt_id);
+            }
+        }
 
+        let panic_msg = "Injected failure".to_string();
+        let error = ActionError {
+            index: actions
+                .iter()
+                .position(|action| matches!(action, Action::FunctionCall(_)))
+                .map(|index| index as u64),
+            kind: ActionErrorKind::FunctionCallError(FunctionCallError::HostError(
+                HostError::GuestPanic { panic_msg },
+            )),
+        };
+        let outcome = ExecutionOutcome {
+            status: ExecutionStatus::Failure(error.into()),
+            receipt_ids,
+            executor_id: receipt.receiver_id.clone(),
+            ..Default::default()
+        };
+        let state_root = self.cur_block.state_root;
+        self.state_roots.insert(receipt.receipt_id, (state_root, state_root));
+        self.last_outcomes.push(receipt.receipt_id);
+        self.outcomes.insert(receipt.receipt_id, outcome);
+        self.profile.insert(receipt.receipt_id, GasProfile::default());
+        new_receipts
+    }
+
+    /// `ExecutionOutcome` doesn't carry the gas profile, and the runtime adds up the costs of all
+    /// receipts of a chunk into one `ProfileData`. To attribute the costs, the transactions and
+    /// receipts of the block are replayed one at a time on top of the previous state, in a copy of
+    /// the state which is dropped afterwards.
+    #[cfg(feature = "profile")]
+    fn record_profiles(
+        &mut self,
+        receipts: &[Receipt],
+        transactions: &[SignedTransaction],
+    ) -> Result<(), RuntimeError> {
+        let tries = self.replay_tries();
+        let mut state_root = self.cur_block.state_root;
+        let transactions = transactions.iter().map(|tx| (&[][..], std::slice::from_ref(tx)));
+        let receipts = receipts.iter().map(|receipt| (std::slice::from_ref(receipt), &[][..]));
+        for (receipts, transactions) in transactions.chain(receipts) {
+            let profile_data = ProfileData::new_enabled();
+            let apply_result = self.runtime.apply(
+                tries.get_trie_for_shard(0),
+                state_root,
+                &None,
+                &self.apply_state(profile_data.clone()),
+                receipts,
+                transactions,
+                self.epoch_info_provider.as_ref(),
+            )?;
+            let mut store_update = StoreUpdate::new_with_tries(tries.clone());
+            tries
+                .apply_insertions(&apply_result.trie_changes, 0, &mut store_update)
+                .expect("Unexpected Storage error");
+            store_update.commit().expect("Unexpected io error");
+            state_root = apply_result.state_root;
+            for outcome in apply_result.outcomes {
+                // The conversion of a transaction into a receipt doesn't execute any code, the
+                // profile belongs to the receipt.
+                let profile = if self.transactions.contains_key(&outcome.id) {
+                    GasProfile::default()
+                } else {
+                    GasProfile::from(&profile_data)
+                };
+                self.profile.insert(outcome.id, profile);
+            }
+        }
         Ok(())
     }
 
+    /// Copies the state into a separate in-memory store, where the replays of `record_profiles`
+    /// can commit their changes without adding them to the store of the runtime.
+    #[cfg(feature = "profile")]
+    fn replay_tries(&self) -> ShardTries {
+        let store = create_test_store();
+        let mut store_update = store.store_update();
+        for (key, value) in self.tries.get_store().iter_without_rc_logic(DBCol::ColState) {
+            store_update.set(DBCol::ColState, &key, &value);
+        }
+        store_update.commit().expect("Unexpected io error");
+        ShardTries::new(store, 1)
+    }
+
+    /// Records the change of the balance and of the storage usage of every account changed by
+    /// each transaction or receipt.
+    fn record_account_changes(
+        &mut self,
+        prev_state_root: CryptoHash,
+        state_changes: &[RawStateChangesWithTrieKey],
+    ) {
+        for RawStateChangesWithTrieKey { trie_key, changes } in state_changes {
+            let account_id = match trie_key {
+                TrieKey::Account { account_id } => account_id,
+                _ => continue,
+            };
+            let usage = |account: &Option<Account>| {
+                account.as_ref().map_or((0, 0), |account| (account.amount, account.storage_usage))
+            };
+            let mut prev = usage(&self.account_at(prev_state_root, account_id));
+            for change in changes {
+                let account = change.data.as_ref().map(|data| {
+                    Account::try_from_slice(data).expect("Account in the state is invalid")
+                });
+                let next = usage(&account);
+                if let Some(hash) = Self::hash_of_cause(&change.cause) {
+                    let delta = self
+                        .account_changes
+                        .entry(hash)
+                        .or_default()
+                        .entry(account_id.clone())
+                        .or_default();
+                    delta.0 += next.0 as i128 - prev.0 as i128;
+                    delta.1 += next.1 as i64 - prev.1 as i64;
+                }
+                prev = next;
+            }
+        }
+    }
+
+    fn record_state_changes(&mut self, state_changes: Vec<RawStateChangesWithTrieKey>) {
+        for RawStateChangesWithTrieKey { trie_key, changes } in state_changes {
+            // Only the last value written by each transaction or receipt is kept.
+            let mut values = HashMap::new();
+            for change in changes {
+                if let Some(hash) = Self::hash_of_cause(&change.cause) {
+                    values.insert(hash, change.data);
+                }
+            }
+            for (hash, value) in values {
+                self.state_changes.entry(hash).or_default().push((trie_key.clone(), value));
+            }
+        }
+    }
+
+    /// The transaction or receipt which caused a state change.
+    fn hash_of_cause(cause: &StateChangeCause) -> Option<CryptoHash> {
+        match cause {
+            StateChangeCause::TransactionProcessing { tx_hash } => Some(*tx_hash),
+            StateChangeCause::ActionReceiptProcessingStarted { receipt_hash }
+            | StateChangeCause::ActionReceiptGasReward { receipt_hash }
+            | StateChangeCause::ReceiptProcessing { receipt_hash }
+            | StateChangeCause::PostponedReceipt { receipt_hash } => Some(*receipt_hash),
+            _ => None,
+        }
+    }
+
+    /// Stores the new state. The state it replaces is removed from the store, unless a snapshot or
+    /// a fork of the runtime may still read it.
+    fn commit_trie_changes(&self, trie_changes: &TrieChanges) {
+        if Arc::strong_count(&self.snapshots) > 1 {
+            let mut store_update = StoreUpdate::new_with_tries(self.tries.clone());
+            self.tries
+                .apply_insertions(trie_changes, 0, &mut store_update)
+                .expect("Unexpected Storage error");
+            store_update.commit().expect("Unexpected io error");
+        } else {
+            let (store_update, _) =
+                self.tries.apply_all(trie_changes, 0).expect("Unexpected Storage error");
+            store_update.commit().expect("Unexpected io error");
+        }
+    }
+
+    pub(crate) fn account_at(&self, state_root: CryptoHash, account_id: &str) -> Option<Account> {
+        let trie_update = self.tries.new_trie_update(0, state_root);
+        get_account(&trie_update, &account_id.to_string()).expect("Unexpected Storage error")
+    }
+
     /// Produce num_of_blocks blocks.
     /// # Examples
     ///
@@ -327,20 +1166,61 @@ impl RuntimeStandalone {
         Ok(())
     }
 
+    /// Jumps ahead in time without executing blocks. The block height advances by the number of
+    /// blocks which would have been produced during `duration`. Pending receipts are executed in
+    /// the next produced block. If `block_prod_time` is zero, only the timestamp moves.
+    ///
+    /// # Examples
+    /// ```
+    /// use near_sdk_sim::runtime::init_runtime;
+    /// let (mut runtime, _, _) = init_runtime(None);
+    /// runtime.fast_forward(7 * 24 * 60 * 60 * 1_000_000_000);
+    /// assert_eq!(runtime.current_block().block_timestamp, 7 * 24 * 60 * 60 * 1_000_000_000);
+    /// assert_eq!(runtime.current_block().block_height, 7 * 24 * 60 * 60);
+    /// ```
+    pub fn fast_forward(&mut self, duration: Duration) {
+        let blocks = duration.checked_div(self.genesis.block_prod_time).unwrap_or(0);
+        self.set_block_height(self.cur_block.block_height + blocks);
+        self.set_block_timestamp(self.cur_block.block_timestamp + duration);
+    }
+
+    /// Sets the timestamp of the current block. Panics if the timestamp is in the past.
+    pub fn set_block_timestamp(&mut self, block_timestamp: u64) {
+        assert!(
+            block_timestamp >= self.cur_block.block_timestamp,
+            "Block timestamp {} is before the current timestamp {}",
+            block_timestamp,
+            self.cur_block.block_timestamp
+        );
+        self.cur_block.block_timestamp = block_timestamp;
+    }
+
+    /// Sets the height of the current block and updates the epoch height accordingly. Panics if
+    /// the height is below the current height.
+    pub fn set_block_height(&mut self, block_height: BlockHeight) {
+        assert!(
+            block_height >= self.cur_block.block_height,
+            "Block height {} is below the current height {}",
+            block_height,
+            self.cur_block.block_height
+        );
+        self.cur_block.block_height = block_height;
+        self.cur_block.epoch_height = block_height / self.genesis.epoch_length;
+        self.deliver_receipts();
+    }
+
     /// Force alter account and change state_root.
     pub fn force_account_update(&mut self, account_id: AccountId, account: &Account) {
         let mut trie_update = self.tries.new_trie_update(0, self.cur_block.state_root);
         set_account(&mut trie_update, String::from(account_id), account);
         trie_update.commit(StateChangeCause::ValidatorAccountsUpdate);
         let (trie_changes, _) = trie_update.finalize().expect("Unexpected Storage error");
-        let (store_update, new_root) = self.tries.apply_all(&trie_changes, 0).unwrap();
-        store_update.commit().expect("No io errors expected");
-        self.cur_block.state_root = new_root;
+        self.commit_trie_changes(&trie_changes);
+        self.cur_block.state_root = trie_changes.new_root;
     }
 
     pub fn view_account(&self, account_id: &str) -> Option<Account> {
-        let trie_update = self.tries.new_trie_update(0, self.cur_block.state_root);
-        get_account(&trie_update, &account_id.to_string()).expect("Unexpected Storage error")
+        self.account_at(self.cur_block.state_root, account_id)
     }
 
     pub fn view_access_key(&self, account_id: &str, public_key: &PublicKey) -> Option<AccessKey> {
@@ -349,6 +1229,49 @@ impl RuntimeStandalone {
             .expect("Unexpected Storage error")
     }
 
+    /// Compares the contract storage of the account between two states, e.g. the state roots of
+    /// the current block before and after a call. The state of an earlier block is only kept while
+    /// a [`Snapshot`] is alive, so take one before reading the first state root.
+    ///
+    /// # Examples
+    /// ```
+    /// use near_sdk_sim::runtime::init_runtime;
+    /// use near_sdk_sim::state_diff::KeyDecoder;
+    /// let (mut runtime, _, _) = init_runtime(None);
+    /// let _snapshot = runtime.snapshot();
+    /// let before = runtime.current_block().state_root();
+    /// runtime.produce_block().unwrap();
+    /// let after = runtime.current_block().state_root();
+    /// let diff = runtime.state_diff(&before, &after, "root");
+    /// assert!(diff.with_decoder(KeyDecoder::new().prefix(b"m", "balances")).is_empty());
+    /// ```
+    pub fn state_diff(
+        &self,
+        before_root: &CryptoHash,
+        after_root: &CryptoHash,
+        account_id: &str,
+    ) -> StateDiff {
+        StateDiff::new(
+            self.contract_data(before_root, account_id),
+            self.contract_data(after_root, account_id),
+        )
+    }
+
+    fn contract_data(
+        &self,
+        state_root: &CryptoHash,
+        account_id: &str,
+    ) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        let query = get_raw_prefix_for_contract_data(&account_id.to_string(), &[]);
+        let trie = self.tries.get_trie_for_shard(0);
+        let mut iter = trie.iter(state_root).expect("Unexpected Storage error");
+        iter.seek(&query).expect("Unexpected Storage error");
+        iter.map(|item| item.expect("Unexpected Storage error"))
+            .take_while(|(key, _)| key.starts_with(&query))
+            .map(|(key, value)| (key[query.len()..].to_vec(), value))
+            .collect()
+    }
+
     /// Returns a ViewResult containing the value or error and any logs
     pub fn view_method_call(&self, account_id: &str, method_name: &str, args: &[u8]) -> ViewResult {
         let trie_update = self.tries.new_trie_update(0, self.cur_block.state_root);
@@ -392,10 +1315,72 @@ impl RuntimeStandalone {
         &self.cur_block
     }
 
+    /// Receipts which will be executed in the next block.
     pub fn pending_receipts(&self) -> &[Receipt] {
         &self.pending_receipts
     }
 
+    /// Receipts which are delayed to a later block, with the height of the block in which they
+    /// will be executed.
+    pub fn in_flight_receipts(&self) -> &[(BlockHeight, Receipt)] {
+        &self.in_flight_receipts
+    }
+
+    /// Whether there are receipts which are not executed yet.
+    pub fn has_pending_receipts(&self) -> bool {
+        !self.pending_receipts.is_empty() || !self.in_flight_receipts.is_empty()
+    }
+
+    /// Returns the shard of the account, see [`GenesisConfig::shard_assignment`].
+    pub fn shard_of(&self, account_id: &str) -> ShardId {
+        self.genesis.shard_assignment.shard_of(account_id, self.genesis.num_shards)
+    }
+
+    /// Delays a receipt which is not executed yet by the given number of blocks, e.g. to execute
+    /// a callback after another receipt it races with. Panics if there is no such receipt.
+    pub fn delay_receipt(&mut self, receipt_id: &CryptoHash, blocks: BlockHeight) {
+        if let Some(index) =
+            self.pending_receipts.iter().position(|receipt| &receipt.receipt_id == receipt_id)
+        {
+            let receipt = self.pending_receipts.remove(index);
+            self.in_flight_receipts.push((self.cur_block.block_height + blocks, receipt));
+            return;
+        }
+        let (block_height, _) = self
+            .in_flight_receipts
+            .iter_mut()
+            .find(|(_, receipt)| &receipt.receipt_id == receipt_id)
+            .unwrap_or_else(|| panic!("No pending receipt {}", receipt_id));
+        *block_height += blocks;
+    }
+
+    /// Moves the receipts which arrive by the current block to the pending receipts.
+    fn deliver_receipts(&mut self) {
+        let block_height = self.cur_block.block_height;
+        let (arrived, in_flight): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight_receipts)
+            .into_iter()
+            .partition(|(arrival_height, _)| *arrival_height <= block_height);
+        self.in_flight_receipts = in_flight;
+        self.pending_receipts.extend(arrived.into_iter().map(|(_, receipt)| receipt));
+    }
+
+    /// Makes the pending action receipt with the given ID fail when it is due to be executed,
+    /// as if the called method panicked with "Injected failure". The receipt is not executed and
+    /// burns no gas. Its deposit and prepaid gas are refunded and callbacks waiting for it
+    /// receive a failed promise result. A receipt which waits for the results of other promises
+    /// fails without waiting for them.
+    pub fn inject_failure(&mut self, receipt_id: &CryptoHash) {
+        let receipt = self
+            .pending_receipts
+            .iter()
+            .find(|receipt| &receipt.receipt_id == receipt_id)
+            .unwrap_or_else(|| panic!("No pending receipt {}", receipt_id));
+        if let ReceiptEnum::Data(_) = receipt.receipt {
+            panic!("Receipt {} is a data receipt", receipt_id);
+        }
+        self.failing_receipts.insert(*receipt_id);
+    }
+
     fn prepare_transactions(tx_pool: &mut TransactionPool) -> Vec<SignedTransaction> {
         let mut res = vec![];
         let mut pool_iter = tx_pool.pool_iterator();
@@ -427,6 +1412,13 @@ mod tests {
         foo._private();
     }
 
+    #[test]
+    fn test_costs_in_profile_order() {
+        assert!(EXT_COSTS.iter().enumerate().all(|(i, ext)| *ext as usize == i));
+        assert!(ACTION_COSTS.iter().enumerate().all(|(i, action)| *action as usize == i));
+        assert_eq!(ACTION_COSTS.len(), ActionCosts::count());
+    }
+
     #[test]
     fn single_block() {
         let (mut runtime, signer, _) = init_runtime(None);
@@ -446,6 +1438,38 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn block_applied_once() {
+        let (mut runtime, signer, _) = init_runtime(None);
+        let prev_state_root = runtime.current_block().state_root();
+        let hashes: Vec<_> = ["alice", "bob"]
+            .iter()
+            .enumerate()
+            .map(|(i, account_id)| {
+                runtime.send_tx(SignedTransaction::create_account(
+                    i as u64 + 1,
+                    signer.account_id.clone(),
+                    account_id.to_string(),
+                    100,
+                    signer.public_key(),
+                    &signer,
+                    CryptoHash::default(),
+                ))
+            })
+            .collect();
+        runtime.produce_block().unwrap();
+        let state_roots = (prev_state_root, runtime.current_block().state_root());
+        for hash in hashes.iter() {
+            assert_eq!(runtime.state_roots_of_outcome(hash), Some(state_roots));
+            assert_eq!(runtime.storage_delta_of_outcome(hash), Some(0));
+        }
+        runtime.produce_block().unwrap();
+        let receipt_id = runtime.outcome(&hashes[0]).unwrap().receipt_ids[0];
+        assert!(runtime.storage_delta_of_outcome(&receipt_id).unwrap() > 0);
+        assert_eq!(runtime.account_change_of_outcome(&receipt_id, "alice").0, 100);
+        assert_eq!(runtime.account_change_of_outcome(&receipt_id, "bob"), (0, 0));
+    }
+
     #[test]
     fn process_all() {
         let (mut runtime, signer, _) = init_runtime(None);
@@ -547,6 +1571,274 @@ mod tests {
         assert_eq!(runtime.view_account(&"root").unwrap().locked, 10000);
     }
 
+    #[test]
+    fn test_snapshot_restore() {
+        let (mut runtime, signer, _) = init_runtime(None);
+        let snapshot = runtime.snapshot();
+        runtime
+            .resolve_tx(SignedTransaction::create_account(
+                1,
+                signer.account_id.clone(),
+                "alice".into(),
+                to_yocto("10"),
+                signer.public_key(),
+                &signer,
+                CryptoHash::default(),
+            ))
+            .unwrap();
+        assert!(runtime.view_account(&"alice").is_some());
+        let root_balance = runtime.view_account(&"root").unwrap().amount;
+
+        runtime.restore(&snapshot);
+        assert_eq!(runtime.view_account(&"alice"), None);
+        assert!(runtime.view_account(&"root").unwrap().amount > root_balance);
+        assert_eq!(runtime.current_block().block_height, snapshot.block().block_height);
+    }
+
+    #[test]
+    fn test_restore_records() {
+        let (mut runtime, signer, _) = init_runtime(None);
+        let validators = vec![AccountInfo {
+            account_id: "alice".into(),
+            public_key: signer.public_key(),
+            amount: 100,
+        }];
+        runtime.set_validators(validators.clone());
+        let create_account = |nonce, account_id: &str| {
+            SignedTransaction::create_account(
+                nonce,
+                signer.account_id.clone(),
+                account_id.into(),
+                100,
+                signer.public_key(),
+                &signer,
+                CryptoHash::default(),
+            )
+        };
+        let (first_hash, _) = runtime.resolve_tx(create_account(1, "alice")).unwrap();
+        let pending_hash = runtime.send_tx(create_account(2, "bob"));
+        let snapshot = runtime.snapshot();
+        let last_outcomes = runtime.last_outcomes.clone();
+
+        runtime.set_validators(vec![]);
+        let (hash, _) = runtime.resolve_tx(create_account(3, "carol")).unwrap();
+        assert!(runtime.outcome(&pending_hash).is_some());
+        assert!(runtime.outcome(&hash).is_some());
+
+        runtime.restore(&snapshot);
+        assert!(runtime.outcome(&first_hash).is_some());
+        assert!(runtime.outcome(&hash).is_none());
+        assert!(runtime.transaction(&hash).is_none());
+        assert!(runtime.outcome(&pending_hash).is_none());
+        assert_eq!(runtime.last_outcomes, last_outcomes);
+        assert_eq!(runtime.validators(), &validators[..]);
+        // The transaction sent before the snapshot is still pending.
+        runtime.produce_block().unwrap();
+        assert!(runtime.outcome(&pending_hash).is_some());
+    }
+
+    #[test]
+    fn test_fork() {
+        let (mut runtime, signer, _) = init_runtime(None);
+        let mut fork = runtime.fork();
+        fork.resolve_tx(SignedTransaction::create_account(
+            1,
+            signer.account_id.clone(),
+            "alice".into(),
+            to_yocto("10"),
+            signer.public_key(),
+            &signer,
+            CryptoHash::default(),
+        ))
+        .unwrap();
+        assert!(fork.view_account(&"alice").is_some());
+        assert_eq!(runtime.view_account(&"alice"), None);
+
+        // The same nonce can be used in the original runtime.
+        runtime
+            .resolve_tx(SignedTransaction::create_account(
+                1,
+                signer.account_id.clone(),
+                "bob".into(),
+                to_yocto("10"),
+                signer.public_key(),
+                &signer,
+                CryptoHash::default(),
+            ))
+            .unwrap();
+        assert!(runtime.view_account(&"bob").is_some());
+        assert_eq!(fork.view_account(&"bob"), None);
+    }
+
+    #[test]
+    fn test_previous_state_kept_while_snapshot_alive() {
+        let (mut runtime, signer, _) = init_runtime(None);
+        let create_account = |nonce, account_id: &str| {
+            SignedTransaction::create_account(
+                nonce,
+                signer.account_id.clone(),
+                account_id.into(),
+                to_yocto("10"),
+                signer.public_key(),
+                &signer,
+                CryptoHash::default(),
+            )
+        };
+        let snapshot = runtime.snapshot();
+        let genesis_root = runtime.current_block().state_root();
+        runtime.resolve_tx(create_account(1, "alice")).unwrap();
+        assert!(runtime.account_at(genesis_root, "root").is_some());
+
+        drop(snapshot);
+        let prev_root = runtime.current_block().state_root();
+        runtime.resolve_tx(create_account(2, "bob")).unwrap();
+        let trie_update = runtime.tries.new_trie_update(0, prev_root);
+        assert!(get_account(&trie_update, &"root".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_hash_source() {
+        assert_eq!(HashSource::Zero.hash_at(5), CryptoHash::default());
+
+        let sequence = HashSource::Sequence(vec![hash(b"a"), hash(b"b")]);
+        assert_eq!(sequence.hash_at(0), hash(b"a"));
+        assert_eq!(sequence.hash_at(3), hash(b"b"));
+        assert_eq!(HashSource::Sequence(vec![]).hash_at(3), CryptoHash::default());
+
+        let from_seed = HashSource::FromSeed(b"seed".to_vec());
+        assert_eq!(from_seed.hash_at(1), from_seed.hash_at(1));
+        assert_ne!(from_seed.hash_at(1), from_seed.hash_at(2));
+        assert_ne!(from_seed.hash_at(1), HashSource::FromSeed(b"other".to_vec()).hash_at(1));
+
+        let custom = HashSource::Custom(Arc::new(|height| hash(&height.to_le_bytes())));
+        assert_eq!(custom.hash_at(7), hash(&7u64.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_block_hash_changes_receipt_ids() {
+        let receipt_id = |block_hash: HashSource| {
+            let mut genesis = GenesisConfig::default();
+            genesis.block_hash = block_hash;
+            let (mut runtime, signer, _) = init_runtime(Some(genesis));
+            let (receipt_id, _) = runtime
+                .resolve_tx(SignedTransaction::create_account(
+                    1,
+                    signer.account_id.clone(),
+                    "alice".into(),
+                    to_yocto("10"),
+                    signer.public_key(),
+                    &signer,
+                    CryptoHash::default(),
+                ))
+                .unwrap();
+            receipt_id
+        };
+        assert_eq!(receipt_id(HashSource::Zero), receipt_id(HashSource::Zero));
+        assert_ne!(receipt_id(HashSource::Zero), receipt_id(HashSource::FromSeed(vec![1])));
+    }
+
+    #[test]
+    fn test_set_validators() {
+        let (mut runtime, signer, _) = init_runtime(None);
+        assert!(runtime.validators().is_empty());
+        let validator = AccountInfo {
+            account_id: "validator".to_string(),
+            public_key: signer.public_key(),
+            amount: to_yocto("100"),
+        };
+        runtime.set_validators(vec![validator.clone()]);
+        assert_eq!(runtime.validators(), &[validator]);
+        assert_eq!(runtime.fork().validators().len(), 1);
+    }
+
+    #[test]
+    fn test_shard_assignment() {
+        let shards: HashMap<_, _> = vec![("alice".to_string(), 2)].into_iter().collect();
+        let assignment = ShardAssignment::Accounts(shards);
+        assert_eq!(assignment.shard_of("alice", 3), 2);
+        assert!(assignment.shard_of("bob", 3) < 3);
+        assert_eq!(ShardAssignment::Hash.shard_of("bob", 1), 0);
+        let custom = ShardAssignment::Custom(Arc::new(|account_id| account_id.len() as u64 % 2));
+        assert_eq!(custom.shard_of("bob", 2), 1);
+    }
+
+    #[test]
+    fn test_cross_shard_delay() {
+        let mut genesis = GenesisConfig::default();
+        genesis.num_shards = 2;
+        genesis.shard_assignment = ShardAssignment::Accounts(
+            vec![("root".to_string(), 0), ("alice".to_string(), 1)].into_iter().collect(),
+        );
+        genesis.cross_shard_delay = 2;
+        let (mut runtime, signer, _) = init_runtime(Some(genesis));
+        let hash = runtime.send_tx(SignedTransaction::create_account(
+            1,
+            signer.account_id.clone(),
+            "alice".into(),
+            to_yocto("1"),
+            signer.public_key(),
+            &signer,
+            CryptoHash::default(),
+        ));
+        runtime.produce_block().unwrap();
+        assert!(runtime.pending_receipts().is_empty());
+        assert_eq!(runtime.in_flight_receipts()[0].0, 3);
+        runtime.produce_block().unwrap();
+        assert!(runtime.pending_receipts().is_empty());
+        runtime.produce_block().unwrap();
+        assert_eq!(runtime.pending_receipts().len(), 1);
+        let receipt_id = runtime.pending_receipts()[0].receipt_id;
+        runtime.delay_receipt(&receipt_id, 1);
+        runtime.produce_block().unwrap();
+        assert!(runtime.outcome(&receipt_id).is_none());
+
+        runtime.process_all().unwrap();
+        assert!(runtime.outcome(&receipt_id).is_some());
+        assert!(runtime.view_account("alice").is_some());
+        assert!(!runtime.has_pending_receipts());
+        assert!(runtime.outcome(&hash).is_some());
+    }
+
+    #[test]
+    fn test_time_travel() {
+        let mut genesis = GenesisConfig::default();
+        genesis.epoch_length = 10;
+        let (mut runtime, _, _) = init_runtime(Some(genesis));
+        runtime.produce_block().unwrap();
+
+        runtime.fast_forward(25 * DEFAULT_BLOCK_PROD_TIME + 1);
+        assert_eq!(runtime.current_block().block_height, 26);
+        assert_eq!(runtime.current_block().epoch_height, 2);
+        assert_eq!(runtime.current_block().block_timestamp, 26 * DEFAULT_BLOCK_PROD_TIME + 1);
+
+        runtime.produce_block().unwrap();
+        assert_eq!(runtime.current_block().block_height, 27);
+        assert_eq!(runtime.current_block().block_timestamp, 27 * DEFAULT_BLOCK_PROD_TIME + 1);
+
+        runtime.set_block_height(100);
+        runtime.set_block_timestamp(1_000 * DEFAULT_BLOCK_PROD_TIME);
+        assert_eq!(runtime.current_block().epoch_height, 10);
+        assert_eq!(runtime.current_block().block_timestamp, 1_000 * DEFAULT_BLOCK_PROD_TIME);
+    }
+
+    #[test]
+    fn test_fast_forward_without_block_prod_time() {
+        let mut genesis = GenesisConfig::default();
+        genesis.block_prod_time = 0;
+        let (mut runtime, _, _) = init_runtime(Some(genesis));
+        runtime.fast_forward(DEFAULT_BLOCK_PROD_TIME);
+        assert_eq!(runtime.current_block().block_height, 0);
+        assert_eq!(runtime.current_block().block_timestamp, DEFAULT_BLOCK_PROD_TIME);
+    }
+
+    #[test]
+    #[should_panic(expected = "Block height 1 is below the current height 2")]
+    fn test_set_block_height_backwards() {
+        let (mut runtime, _, _) = init_runtime(None);
+        runtime.produce_blocks(2).unwrap();
+        runtime.set_block_height(1);
+    }
+
     #[test]
     fn can_produce_many_blocks_without_stack_overflow() {
         let (mut runtime, _signer, _) = init_runtime(None);
@@ -0,0 +1,210 @@
+//! Comparison of the contract storage of an account between two states, to check which keys a
+//! call wrote and catch accidental writes or leaked storage.
+
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Keys added, removed and modified in the contract storage of an account. Created with
+/// [`RuntimeStandalone::state_diff`].
+///
+/// [`RuntimeStandalone::state_diff`]: crate::runtime::RuntimeStandalone::state_diff
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Keys with their new values.
+    pub added: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Keys with their previous values.
+    pub removed: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Keys with their previous and new values.
+    pub modified: BTreeMap<Vec<u8>, (Vec<u8>, Vec<u8>)>,
+    decoder: KeyDecoder,
+}
+
+impl StateDiff {
+    pub(crate) fn new(
+        mut before: BTreeMap<Vec<u8>, Vec<u8>>,
+        after: BTreeMap<Vec<u8>, Vec<u8>>,
+    ) -> Self {
+        let mut diff = Self::default();
+        for (key, value) in after {
+            match before.remove(&key) {
+                None => {
+                    diff.added.insert(key, value);
+                }
+                Some(old_value) if old_value != value => {
+                    diff.modified.insert(key, (old_value, value));
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = before;
+        diff
+    }
+
+    /// Uses the decoder to display the keys of the diff.
+    pub fn with_decoder(mut self, decoder: KeyDecoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Whether no key was changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// All added, removed and modified keys.
+    pub fn changed_keys(&self) -> BTreeSet<&[u8]> {
+        self.added
+            .keys()
+            .chain(self.removed.keys())
+            .chain(self.modified.keys())
+            .map(|key| key.as_slice())
+            .collect()
+    }
+
+    /// Asserts that exactly the given keys were changed.
+    pub fn assert_changed_keys<K: AsRef<[u8]>>(&self, keys: &[K]) {
+        let expected: BTreeSet<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+        assert_eq!(self.changed_keys(), expected, "Unexpected keys changed:\n{}", self);
+    }
+
+    /// Asserts that all changed keys start with one of the prefixes, e.g. the prefixes of the
+    /// collections which the call is expected to update.
+    pub fn assert_only_prefixes<P: AsRef<[u8]>>(&self, prefixes: &[P]) {
+        let unexpected: Vec<_> = self
+            .changed_keys()
+            .into_iter()
+            .filter(|key| !prefixes.iter().any(|prefix| key.starts_with(prefix.as_ref())))
+            .map(|key| self.decoder.decode(key))
+            .collect();
+        assert!(unexpected.is_empty(), "Unexpected keys changed: {}", unexpected.join(", "));
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.added {
+            writeln!(f, "+ {} = {}", self.decoder.decode(key), format_bytes(value))?;
+        }
+        for (key, value) in &self.removed {
+            writeln!(f, "- {} = {}", self.decoder.decode(key), format_bytes(value))?;
+        }
+        for (key, (old_value, value)) in &self.modified {
+            writeln!(
+                f,
+                "~ {} = {} -> {}",
+                self.decoder.decode(key),
+                format_bytes(old_value),
+                format_bytes(value)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Names the storage prefixes of `collections`, so that keys are displayed as the collection
+/// name and the rest of the key, e.g. `balances["alice"]` instead of raw bytes.
+///
+/// # Examples
+/// ```
+/// use near_sdk_sim::state_diff::KeyDecoder;
+///
+/// let decoder = KeyDecoder::new().prefix(b"b", "balances");
+/// assert_eq!(decoder.decode(b"balice"), "balances[\"alice\"]");
+/// assert_eq!(decoder.decode(b"STATE"), "\"STATE\"");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyDecoder {
+    prefixes: Vec<(Vec<u8>, String)>,
+}
+
+impl KeyDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the collection stored under the prefix. If several prefixes match a key, the
+    /// longest one is used.
+    pub fn prefix(mut self, prefix: &[u8], name: &str) -> Self {
+        self.prefixes.push((prefix.to_vec(), name.to_string()));
+        self
+    }
+
+    /// Formats the key using the name of the longest matching prefix.
+    pub fn decode(&self, key: &[u8]) -> String {
+        match self
+            .prefixes
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+        {
+            Some((prefix, name)) if prefix.len() == key.len() => name.clone(),
+            Some((prefix, name)) => format!("{}[{}]", name, format_bytes(&key[prefix.len()..])),
+            None => format_bytes(key),
+        }
+    }
+}
+
+/// Formats printable ASCII as a quoted string and other bytes as hex.
+fn format_bytes(bytes: &[u8]) -> String {
+    if bytes.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        format!("{:?}", String::from_utf8_lossy(bytes))
+    } else {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("0x{}", hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{init_simulator, to_yocto, DEFAULT_GAS};
+
+    #[test]
+    fn diff_maps() {
+        let before: BTreeMap<_, _> =
+            vec![(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())]
+                .into_iter()
+                .collect();
+        let after: BTreeMap<_, _> =
+            vec![(b"b".to_vec(), b"3".to_vec()), (b"c".to_vec(), vec![0, 1])].into_iter().collect();
+        let diff = StateDiff::new(before, after);
+        assert_eq!(diff.removed.keys().collect::<Vec<_>>(), vec![b"a"]);
+        assert_eq!(diff.modified[&b"b".to_vec()], (b"2".to_vec(), b"3".to_vec()));
+        assert_eq!(diff.added[&b"c".to_vec()], vec![0, 1]);
+        diff.assert_changed_keys(&[b"a", b"b", b"c"]);
+        assert_eq!(
+            diff.to_string(),
+            "+ \"c\" = 0x0001\n- \"a\" = \"1\"\n~ \"b\" = \"2\" -> \"3\"\n"
+        );
+    }
+
+    #[test]
+    fn diff_call() {
+        let root = init_simulator(None);
+        let status = root.deploy(
+            include_bytes!("../../examples/status-message/res/status_message.wasm").as_ref(),
+            "status".parse().unwrap(),
+            to_yocto("35"),
+        );
+        let _snapshot = root.borrow_runtime().snapshot();
+        let state_root = || root.borrow_runtime().current_block().state_root();
+        let before = state_root();
+        root.call(status.account_id(), "set_status", br#"{"message": "hi"}"#, DEFAULT_GAS, 0)
+            .assert_success();
+        let after = state_root();
+        let diff = root.borrow_runtime().state_diff(&before, &after, "status");
+        diff.assert_changed_keys(&[b"STATE"]);
+        assert_eq!(diff.added.len(), 1);
+
+        root.call(status.account_id(), "set_status", br#"{"message": "bye"}"#, DEFAULT_GAS, 0)
+            .assert_success();
+        let decoder = KeyDecoder::new().prefix(b"STATE", "state");
+        let latest = state_root();
+        let diff =
+            root.borrow_runtime().state_diff(&after, &latest, "status").with_decoder(decoder);
+        assert_eq!(diff.modified.len(), 1);
+        diff.assert_only_prefixes(&[b"STATE"]);
+        assert!(diff.to_string().starts_with("~ state = "));
+        assert!(root.borrow_runtime().state_diff(&after, &after, "status").is_empty());
+    }
+}
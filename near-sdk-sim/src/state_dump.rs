@@ -0,0 +1,208 @@
+//! Loading of account state exported from a node, so that transactions can be replayed locally
+//! against real contracts and data.
+
+use std::io;
+use std::path::Path;
+
+use near_sdk::borsh::BorshDeserialize;
+use near_sdk::serde_json::{self, Value};
+use near_sdk::AccountId;
+
+use crate::runtime::GenesisConfig;
+use crate::state_record::StateRecord;
+
+/// Format of a state dump file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// JSON array of state records, or a genesis file produced by the node's state dump which
+    /// contains them in the `records` field.
+    Json,
+    /// Borsh-serialized `Vec<(Vec<u8>, Vec<u8>)>` of raw trie keys and values, as iterated from
+    /// the state of a node.
+    Borsh,
+}
+
+/// Selects the records loaded from a state dump.
+///
+/// Postponed and delayed receipts and received data are never loaded, since they belong to
+/// transactions which were in flight when the dump was taken.
+#[derive(Debug, Clone, Default)]
+pub struct StateFilter {
+    accounts: Vec<String>,
+    data_prefixes: Vec<Vec<u8>>,
+}
+
+impl StateFilter {
+    /// Loads all accounts with all their data.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Loads the given account. Once an account is added, other accounts are skipped.
+    pub fn account(mut self, account_id: AccountId) -> Self {
+        self.accounts.push(account_id.into());
+        self
+    }
+
+    /// Loads contract data with keys starting with the prefix, e.g. the prefix of a `LookupMap`.
+    /// Once a prefix is added, data with other keys is skipped.
+    pub fn data_prefix(mut self, prefix: &[u8]) -> Self {
+        self.data_prefixes.push(prefix.to_vec());
+        self
+    }
+
+    pub fn matches(&self, record: &StateRecord) -> bool {
+        let account_id = match record {
+            StateRecord::Account { account_id, .. }
+            | StateRecord::Contract { account_id, .. }
+            | StateRecord::AccessKey { account_id, .. } => account_id,
+            StateRecord::Data { account_id, data_key, .. } => {
+                if !self.data_prefixes.is_empty()
+                    && !self.data_prefixes.iter().any(|prefix| data_key.starts_with(prefix))
+                {
+                    return false;
+                }
+                account_id
+            }
+            StateRecord::PostponedReceipt(_)
+            | StateRecord::ReceivedData { .. }
+            | StateRecord::DelayedReceipt(_) => return false,
+        };
+        self.accounts.is_empty() || self.accounts.contains(account_id)
+    }
+}
+
+/// Reads the state records matching the filter from a dump file.
+///
+/// The storage usage of the loaded accounts is recomputed from the loaded records when the
+/// genesis state is applied.
+pub fn load_state_records(
+    path: impl AsRef<Path>,
+    format: DumpFormat,
+    filter: &StateFilter,
+) -> io::Result<Vec<StateRecord>> {
+    let bytes = std::fs::read(path)?;
+    let records: Vec<StateRecord> = match format {
+        DumpFormat::Json => {
+            let value: Value = serde_json::from_slice(&bytes)?;
+            let records = match value {
+                Value::Object(mut genesis) => genesis.remove("records").ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Missing `records` field")
+                })?,
+                value => value,
+            };
+            serde_json::from_value(records)?
+        }
+        DumpFormat::Borsh => {
+            let pairs: Vec<(Vec<u8>, Vec<u8>)> = BorshDeserialize::try_from_slice(&bytes)?;
+            pairs
+                .into_iter()
+                .filter_map(|(key, value)| StateRecord::from_raw_key_value(key, value))
+                .collect()
+        }
+    };
+    Ok(records.into_iter().filter(|record| filter.matches(record)).collect())
+}
+
+impl GenesisConfig {
+    /// Adds the state records matching the filter from a dump file to the genesis state.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use near_sdk_sim::runtime::GenesisConfig;
+    /// use near_sdk_sim::state_dump::{DumpFormat, StateFilter};
+    ///
+    /// let mut genesis = GenesisConfig::default();
+    /// let filter = StateFilter::all().account("token.near".parse().unwrap()).data_prefix(b"a");
+    /// genesis.import_state("state_dump.json", DumpFormat::Json, &filter).unwrap();
+    /// let root = near_sdk_sim::init_simulator(Some(genesis));
+    /// ```
+    pub fn import_state(
+        &mut self,
+        path: impl AsRef<Path>,
+        format: DumpFormat,
+        filter: &StateFilter,
+    ) -> io::Result<()> {
+        self.state_records.extend(load_state_records(path, format, filter)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::CryptoHash;
+    use crate::runtime::init_runtime;
+    use crate::test_utils::account_new;
+    use crate::trie_key::TrieKey;
+    use near_sdk::borsh::BorshSerialize;
+    use std::io::Write;
+
+    fn records() -> Vec<StateRecord> {
+        let mut records = vec![];
+        for account_id in &["alice", "bob"] {
+            records.push(StateRecord::Account {
+                account_id: account_id.to_string(),
+                account: account_new(10u128.pow(24), CryptoHash::default()),
+            });
+            for key in &[b"a1", b"b1"] {
+                records.push(StateRecord::Data {
+                    account_id: account_id.to_string(),
+                    data_key: key.to_vec(),
+                    value: b"value".to_vec(),
+                });
+            }
+        }
+        records
+    }
+
+    /// Writes the dump to a new temporary file, which is removed when it is dropped.
+    fn write_dump(bytes: &[u8]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+        file
+    }
+
+    #[test]
+    fn import_json() {
+        let genesis_json = serde_json::json!({ "records": records() });
+        let file = write_dump(genesis_json.to_string().as_bytes());
+        let path = file.path();
+        let filter = StateFilter::all().account("alice".parse().unwrap()).data_prefix(b"a");
+        let loaded = load_state_records(path, DumpFormat::Json, &filter).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(matches!(&loaded[1], StateRecord::Data { data_key, .. } if data_key == b"a1"));
+
+        let mut genesis = GenesisConfig::default();
+        genesis.import_state(path, DumpFormat::Json, &filter).unwrap();
+        let (runtime, _, _) = init_runtime(Some(genesis));
+        // The storage usage only includes the imported data.
+        let storage_usage = runtime.view_account("alice").unwrap().storage_usage;
+        assert_eq!(storage_usage, 100 + 40 + 2 + 5);
+        assert_eq!(runtime.view_account("bob"), None);
+    }
+
+    #[test]
+    fn import_borsh() {
+        let pairs: Vec<(Vec<u8>, Vec<u8>)> = records()
+            .into_iter()
+            .map(|record| match record {
+                StateRecord::Account { account_id, account } => {
+                    (TrieKey::Account { account_id }.to_vec(), account.try_to_vec().unwrap())
+                }
+                StateRecord::Data { account_id, data_key, value } => {
+                    (TrieKey::ContractData { account_id, key: data_key }.to_vec(), value)
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        let file = write_dump(&pairs.try_to_vec().unwrap());
+        let path = file.path();
+        let loaded = load_state_records(path, DumpFormat::Borsh, &StateFilter::all()).unwrap();
+        assert_eq!(loaded.len(), 6);
+
+        let filter = StateFilter::all().account("bob".parse().unwrap());
+        let loaded = load_state_records(path, DumpFormat::Borsh, &filter).unwrap();
+        assert_eq!(loaded.len(), 3);
+    }
+}
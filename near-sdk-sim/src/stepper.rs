@@ -0,0 +1,167 @@
+use std::sync::{Arc, RwLock};
+
+use near_sdk::AccountId;
+
+use crate::{
+    account::Account,
+    hash::CryptoHash,
+    outcome_into_result,
+    receipt::Receipt,
+    runtime::RuntimeStandalone,
+    transaction::{ExecutionStatus, SignedTransaction},
+    types::BlockHeight,
+    ExecutionResult, ViewResult,
+};
+
+/// A transaction which is executed one block at a time, so that the state can be inspected
+/// between the receipts it creates. Created with [`UserTransaction::start`].
+///
+/// # Example:
+///
+/// ```
+/// use near_sdk_sim::to_yocto;
+/// let master_account = near_sdk_sim::init_simulator(None);
+/// let alice = master_account.create_user("alice".parse().unwrap(), to_yocto("10"));
+///
+/// let stepper = master_account.create_transaction(alice.account_id()).transfer(to_yocto("1")).start();
+/// // The transaction is converted into a receipt in the first block.
+/// stepper.step_block();
+/// let receipt_id = stepper.pending_receipts()[0].receipt_id;
+/// assert_eq!(stepper.view_account(alice.account_id()).unwrap().amount, to_yocto("10"));
+///
+/// stepper.inject_failure(&receipt_id);
+/// assert!(!stepper.finish().is_ok());
+/// assert_eq!(alice.account().unwrap().amount, to_yocto("10"));
+/// ```
+///
+/// [`UserTransaction::start`]: crate::UserTransaction::start
+pub struct TransactionStepper {
+    runtime: Arc<RwLock<RuntimeStandalone>>,
+    hash: CryptoHash,
+}
+
+impl TransactionStepper {
+    pub(crate) fn new(
+        runtime: &Arc<RwLock<RuntimeStandalone>>,
+        transaction: SignedTransaction,
+    ) -> Self {
+        let hash = {
+            let mut runtime = runtime.write().unwrap();
+            runtime.last_outcomes = vec![];
+            runtime.send_tx(transaction)
+        };
+        Self { runtime: Arc::clone(runtime), hash }
+    }
+
+    /// Hash of the transaction.
+    pub fn hash(&self) -> CryptoHash {
+        self.hash
+    }
+
+    /// Produces one block and returns the outcomes of the transaction or receipts executed in it.
+    pub fn step_block(&self) -> Vec<ExecutionResult> {
+        let mut runtime = self.runtime.write().unwrap();
+        let start = runtime.last_outcomes.len();
+        runtime.produce_block().unwrap();
+        runtime.last_outcomes[start..]
+            .iter()
+            .map(|id| ExecutionResult::new(runtime.outcome(id).unwrap(), &self.runtime, *id))
+            .collect()
+    }
+
+    /// Receipts which will be executed in the next block.
+    pub fn pending_receipts(&self) -> Vec<Receipt> {
+        self.runtime.read().unwrap().pending_receipts().to_vec()
+    }
+
+    /// Whether the transaction and all receipts created by it have been executed.
+    pub fn is_finished(&self) -> bool {
+        let runtime = self.runtime.read().unwrap();
+        runtime.outcome(&self.hash).is_some() && !runtime.has_pending_receipts()
+    }
+
+    /// Makes the pending receipt with the given ID fail. See [`RuntimeStandalone::inject_failure`].
+    pub fn inject_failure(&self, receipt_id: &CryptoHash) {
+        self.runtime.write().unwrap().inject_failure(receipt_id)
+    }
+
+    /// Delays a receipt by the given number of blocks. See [`RuntimeStandalone::delay_receipt`].
+    pub fn delay_receipt(&self, receipt_id: &CryptoHash, blocks: BlockHeight) {
+        self.runtime.write().unwrap().delay_receipt(receipt_id, blocks)
+    }
+
+    /// Calls a view method on the current state.
+    pub fn view(&self, receiver_id: AccountId, method: &str, args: &[u8]) -> ViewResult {
+        self.runtime.read().unwrap().view_method_call(receiver_id.as_str(), method, args)
+    }
+
+    /// Looks up the account information in the current state.
+    pub fn view_account(&self, account_id: AccountId) -> Option<Account> {
+        self.runtime.read().unwrap().view_account(account_id.as_str())
+    }
+
+    /// Produces blocks until all receipts are executed and returns the final outcome of the
+    /// transaction, the same way as [`UserTransaction::submit`].
+    ///
+    /// [`UserTransaction::submit`]: crate::UserTransaction::submit
+    pub fn finish(self) -> ExecutionResult {
+        while !self.is_finished() {
+            self.step_block();
+        }
+        let runtime = self.runtime.read().unwrap();
+        let mut hash = self.hash;
+        loop {
+            let outcome = runtime.outcome(&hash).unwrap();
+            match outcome.status {
+                ExecutionStatus::SuccessReceiptId(id) => hash = id,
+                _ => return outcome_into_result((hash, outcome), &self.runtime),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{init_simulator, to_yocto};
+
+    #[test]
+    fn step_create_account() {
+        let root = init_simulator(None);
+        let stepper = root
+            .create_transaction("alice".parse().unwrap())
+            .create_account()
+            .transfer(to_yocto("10"))
+            .start();
+        assert!(!stepper.is_finished());
+
+        let outcomes = stepper.step_block();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].executor_id(), "root");
+        assert_eq!(stepper.pending_receipts().len(), 1);
+        assert_eq!(stepper.view_account("alice".parse().unwrap()), None);
+
+        let outcomes = stepper.step_block();
+        assert_eq!(outcomes[0].executor_id(), "alice");
+        assert!(stepper.view_account("alice".parse().unwrap()).is_some());
+
+        stepper.finish().assert_success();
+    }
+
+    #[test]
+    fn inject_failure() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("10"));
+        let stepper = root.create_transaction(alice.account_id()).transfer(to_yocto("5")).start();
+        stepper.step_block();
+        let balance = root.account().unwrap().amount;
+        let receipt = stepper.pending_receipts()[0].clone();
+        stepper.inject_failure(&receipt.receipt_id);
+
+        let res = stepper.finish();
+        res.assert_failure_contains("Injected failure");
+        assert_eq!(alice.account().unwrap().amount, to_yocto("10"));
+        // The receipt is not executed, so the deposit and all the prepaid gas are refunded.
+        assert!(root.account().unwrap().amount > balance + to_yocto("5"));
+        assert_eq!(root.borrow_runtime().receipt(&receipt.receipt_id), Some(receipt));
+    }
+}
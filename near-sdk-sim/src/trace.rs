@@ -0,0 +1,289 @@
+//! Execution traces of transactions, which can be written to JSON files or printed as a tree to
+//! debug failing tests, e.g. as CI artifacts.
+
+use core::fmt;
+
+use near_sdk::borsh::BorshDeserialize;
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json::{self, json, Value};
+use near_sdk::Gas;
+
+use crate::account::{AccessKey, Account};
+use crate::hash::{hash, CryptoHash};
+use crate::receipt::ReceiptEnum;
+use crate::runtime::RuntimeStandalone;
+use crate::transaction::{Action, ExecutionOutcome, ExecutionStatus};
+use crate::trie_key::TrieKey;
+
+/// A transaction or receipt with its actions, result and state changes, and the traces of the
+/// receipts it created.
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReceiptTrace {
+    pub id: String,
+    pub predecessor_id: String,
+    pub receiver_id: String,
+    /// Actions in the JSON format of the RPC, with function call arguments decoded from JSON if
+    /// possible and contract code replaced by its hash and length.
+    pub actions: Vec<Value>,
+    pub logs: Vec<String>,
+    pub gas_burnt: Gas,
+    pub tokens_burnt: U128,
+    pub status: Value,
+    /// Final values of the keys changed by this receipt, sorted by account.
+    pub state_changes: Vec<StateChangeTrace>,
+    pub children: Vec<ReceiptTrace>,
+}
+
+/// A change of a single key of the state. The `value` is `None` if the key was removed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StateChangeTrace {
+    pub account_id: String,
+    /// One of `account`, `access_key`, `contract_code` or `data`.
+    pub kind: &'static str,
+    /// Public key of an access key, or base64 encoded key of contract data.
+    pub key: Option<String>,
+    pub value: Option<Value>,
+}
+
+impl ReceiptTrace {
+    pub(crate) fn new(
+        runtime: &RuntimeStandalone,
+        hash: &CryptoHash,
+        outcome: &ExecutionOutcome,
+    ) -> Self {
+        let (predecessor_id, receiver_id, actions) = if let Some(tx) = runtime.transaction(hash) {
+            let tx = tx.transaction;
+            (tx.signer_id, tx.receiver_id, tx.actions)
+        } else if let Some(receipt) = runtime.receipt(hash) {
+            let actions = match receipt.receipt {
+                ReceiptEnum::Action(action_receipt) => action_receipt.actions,
+                ReceiptEnum::Data(_) => vec![],
+            };
+            (receipt.predecessor_id, receipt.receiver_id, actions)
+        } else {
+            // Receipts of transactions sent to the signer are executed together with the
+            // transaction and are not recorded, so they are taken from the transaction.
+            match runtime.parent_of_outcome(hash).and_then(|parent| runtime.transaction(&parent)) {
+                Some(tx) => {
+                    let tx = tx.transaction;
+                    (tx.signer_id, outcome.executor_id.clone(), tx.actions)
+                }
+                None => (outcome.executor_id.clone(), outcome.executor_id.clone(), vec![]),
+            }
+        };
+        let mut state_changes: Vec<_> = runtime
+            .state_changes_of_outcome(hash)
+            .into_iter()
+            .filter_map(|(key, value)| StateChangeTrace::new(key, value))
+            .collect();
+        state_changes
+            .sort_by(|a, b| (&a.account_id, a.kind, &a.key).cmp(&(&b.account_id, b.kind, &b.key)));
+        Self {
+            id: hash.to_string(),
+            predecessor_id,
+            receiver_id,
+            actions: actions.iter().map(action_to_json).collect(),
+            logs: outcome.logs.clone(),
+            gas_burnt: outcome.gas_burnt,
+            tokens_burnt: outcome.tokens_burnt.into(),
+            status: status_to_json(&outcome.status),
+            state_changes,
+            children: outcome
+                .receipt_ids
+                .iter()
+                .filter_map(|id| Some(Self::new(runtime, id, &runtime.outcome(id)?)))
+                .collect(),
+        }
+    }
+
+    /// Serializes the trace as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    fn fmt_tree(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        prefix: &str,
+        child_prefix: &str,
+    ) -> fmt::Result {
+        writeln!(f, "{}{} -> {} {}", prefix, self.predecessor_id, self.receiver_id, self.id)?;
+        let mut rows: Vec<String> = self.actions.iter().map(action_summary).collect();
+        rows.extend(self.logs.iter().map(|log| format!("log: {}", log)));
+        rows.extend(self.state_changes.iter().map(|change| change.to_string()));
+        rows.push(format!(
+            "gas: {}.{:03} Tgas, {} yN burnt",
+            self.gas_burnt / 10u64.pow(12),
+            self.gas_burnt / 10u64.pow(9) % 1000,
+            self.tokens_burnt.0
+        ));
+        rows.push(format!("status: {}", self.status));
+        let count = rows.len() + self.children.len();
+        for (i, row) in rows.iter().enumerate() {
+            let branch = if i + 1 == count { "└── " } else { "├── " };
+            writeln!(f, "{}{}{}", child_prefix, branch, row)?;
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let (branch, indent) = if rows.len() + i + 1 == count {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            child.fmt_tree(
+                f,
+                &format!("{}{}", child_prefix, branch),
+                &format!("{}{}", child_prefix, indent),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the trace as a tree of receipts.
+impl fmt::Display for ReceiptTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(f, "", "")
+    }
+}
+
+impl StateChangeTrace {
+    fn new(key: TrieKey, value: Option<Vec<u8>>) -> Option<Self> {
+        let (account_id, kind, key, value) = match key {
+            TrieKey::Account { account_id } => {
+                let value = value.map(|value| {
+                    serde_json::to_value(Account::try_from_slice(&value).unwrap()).unwrap()
+                });
+                (account_id, "account", None, value)
+            }
+            TrieKey::AccessKey { account_id, public_key } => {
+                let value = value.map(|value| {
+                    serde_json::to_value(AccessKey::try_from_slice(&value).unwrap()).unwrap()
+                });
+                (account_id, "access_key", Some(public_key.to_string()), value)
+            }
+            TrieKey::ContractCode { account_id } => {
+                let value =
+                    value.map(|code| json!({ "hash": hash(&code).to_string(), "len": code.len() }));
+                (account_id, "contract_code", None, value)
+            }
+            TrieKey::ContractData { account_id, key } => {
+                let value = value.map(|value| Value::String(near_sdk::base64::encode(&value)));
+                (account_id, "data", Some(near_sdk::base64::encode(&key)), value)
+            }
+            // Postponed receipts and received data are internal to the runtime.
+            _ => return None,
+        };
+        Some(Self { account_id, kind, key, value })
+    }
+}
+
+impl fmt::Display for StateChangeTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind, self.account_id)?;
+        if let Some(key) = &self.key {
+            write!(f, " {}", key)?;
+        }
+        match &self.value {
+            Some(value) => write!(f, " = {}", value),
+            None => write!(f, " removed"),
+        }
+    }
+}
+
+/// Decodes the bytes as JSON, or encodes them as base64 if they are not valid JSON.
+fn bytes_to_json(bytes: &[u8]) -> Value {
+    serde_json::from_slice(bytes).unwrap_or_else(|_| Value::String(near_sdk::base64::encode(bytes)))
+}
+
+fn action_to_json(action: &Action) -> Value {
+    let mut value = serde_json::to_value(action).unwrap();
+    match action {
+        Action::FunctionCall(call) => {
+            value["FunctionCall"]["args"] = bytes_to_json(&call.args);
+        }
+        Action::DeployContract(deploy) => {
+            value["DeployContract"] =
+                json!({ "hash": hash(&deploy.code).to_string(), "len": deploy.code.len() });
+        }
+        _ => {}
+    }
+    value
+}
+
+fn action_summary(action: &Value) -> String {
+    match action.as_object().and_then(|action| action.iter().next()) {
+        Some((name, args)) => match args["method_name"].as_str() {
+            Some(method_name) => format!("{} {}", name, method_name),
+            None => name.clone(),
+        },
+        None => action.to_string(),
+    }
+}
+
+fn status_to_json(status: &ExecutionStatus) -> Value {
+    match status {
+        ExecutionStatus::Unknown => json!("Unknown"),
+        ExecutionStatus::Failure(err) => json!({ "Failure": err.to_string() }),
+        ExecutionStatus::SuccessValue(value) => json!({ "SuccessValue": bytes_to_json(value) }),
+        ExecutionStatus::SuccessReceiptId(id) => json!({ "SuccessReceiptId": id.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{init_simulator, to_yocto, DEFAULT_GAS};
+    use near_sdk::serde_json::{self, Value};
+
+    #[test]
+    fn trace_function_call() {
+        let root = init_simulator(None);
+        let status = root.deploy(
+            include_bytes!("../../examples/status-message/res/status_message.wasm").as_ref(),
+            "status".parse().unwrap(),
+            to_yocto("35"),
+        );
+        let res = root.call(
+            status.account_id(),
+            "set_status",
+            b"{\"message\": \"hello\"}",
+            DEFAULT_GAS,
+            0,
+        );
+        res.assert_success();
+
+        let trace = res.transaction_trace();
+        assert_eq!(trace.predecessor_id, "root");
+        assert_eq!(trace.receiver_id, "status");
+        let receipt = &trace.children[0];
+        assert_eq!(receipt.id, res.trace().id);
+        assert_eq!(receipt.actions[0]["FunctionCall"]["args"]["message"], "hello");
+        assert_eq!(receipt.logs, vec!["root set_status with message hello"]);
+        assert!(receipt
+            .state_changes
+            .iter()
+            .any(|change| change.account_id == "status" && change.kind == "data"));
+        assert!(trace.to_string().contains("FunctionCall set_status"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.json");
+        res.write_trace(&path).unwrap();
+        let written: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["children"][0]["receiver_id"], "status");
+    }
+
+    #[test]
+    fn trace_transaction_to_signer() {
+        let root = init_simulator(None);
+        let res = root.transfer(root.account_id(), 1);
+        res.assert_success();
+
+        // The receipt is executed in the same block as the transaction and is not recorded.
+        let receipt = &res.transaction_trace().children[0];
+        assert_eq!(receipt.predecessor_id, "root");
+        assert_eq!(receipt.receiver_id, "root");
+        assert!(receipt.actions[0].get("Transfer").is_some());
+    }
+}
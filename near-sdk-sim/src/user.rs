@@ -1,10 +1,10 @@
-use std::cell::{Ref, RefCell, RefMut};
 use std::fmt::{Debug, Formatter};
-use std::rc::Rc;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
 
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signer};
 
 use near_sdk::AccountId;
+use near_sdk::Duration;
 use near_sdk::PendingContractTx;
 
 use crate::runtime::init_runtime;
@@ -14,15 +14,53 @@ use crate::{
     hash::CryptoHash,
     outcome_into_result,
     runtime::{GenesisConfig, RuntimeStandalone},
-    transaction::Transaction,
-    types::{Balance, Gas},
-    ExecutionResult, ViewResult,
+    transaction::{SignedTransaction, Transaction},
+    types::{Balance, BlockHeight, Gas},
+    ExecutionResult, TransactionStepper, ViewResult,
 };
 
 pub const DEFAULT_GAS: u64 = 300_000_000_000_000;
 pub const STORAGE_AMOUNT: u128 = 50_000_000_000_000_000_000_000_000;
 
-type Runtime = Rc<RefCell<RuntimeStandalone>>;
+type Runtime = Arc<RwLock<RuntimeStandalone>>;
+
+/// Locks the runtime for reading. Panics instead of deadlocking if it is borrowed mutably, e.g. by
+/// a guard returned by [`UserAccount::borrow_runtime_mut`] which is still alive.
+fn read_runtime(runtime: &Runtime) -> RwLockReadGuard<RuntimeStandalone> {
+    match runtime.try_read() {
+        Ok(guard) => guard,
+        Err(TryLockError::WouldBlock) => panic!(
+            "The runtime is already borrowed mutably, drop the guard returned by \
+             `borrow_runtime_mut` before using the runtime again"
+        ),
+        Err(TryLockError::Poisoned(_)) => panic!("The runtime is poisoned by an earlier panic"),
+    }
+}
+
+/// Locks the runtime for writing. Panics instead of deadlocking if it is borrowed, e.g. by a guard
+/// returned by [`UserAccount::borrow_runtime`] which is still alive.
+fn write_runtime(runtime: &Runtime) -> RwLockWriteGuard<RuntimeStandalone> {
+    match runtime.try_write() {
+        Ok(guard) => guard,
+        Err(TryLockError::WouldBlock) => panic!(
+            "The runtime is already borrowed, drop the guards returned by `borrow_runtime` and \
+             `borrow_runtime_mut` before using the runtime again"
+        ),
+        Err(TryLockError::Poisoned(_)) => panic!("The runtime is poisoned by an earlier panic"),
+    }
+}
+
+/// Executes the transaction and all the receipts it produces while holding a single lock, so that
+/// no other call is executed in between.
+fn execute(runtime: &Runtime, transaction: SignedTransaction) -> ExecutionResult {
+    let res = {
+        let mut runtime = write_runtime(runtime);
+        let res = runtime.resolve_tx(transaction).unwrap();
+        runtime.process_all().unwrap();
+        res
+    };
+    outcome_into_result(res, runtime)
+}
 
 /// A transaction to be signed by the user which created it. Multiple actions can be chained together
 /// and then signed and sumited to be executed.
@@ -56,10 +94,19 @@ pub struct UserTransaction {
 impl UserTransaction {
     /// Sign and execute the transaction
     pub fn submit(self) -> ExecutionResult {
-        let res =
-            (*self.runtime).borrow_mut().resolve_tx(self.transaction.sign(&self.signer)).unwrap();
-        (*self.runtime).borrow_mut().process_all().unwrap();
-        outcome_into_result(res, &self.runtime)
+        execute(&self.runtime, self.transaction.sign(&self.signer))
+    }
+
+    /// Overrides the nonce of the transaction, which is the next nonce of the access key by
+    /// default. Transactions with a nonce not greater than the nonce of the key fail.
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.transaction.nonce = nonce;
+        self
+    }
+
+    /// Sign the transaction and return a handle which executes it one block at a time.
+    pub fn start(self) -> TransactionStepper {
+        TransactionStepper::new(&self.runtime, self.transaction.sign(&self.signer))
     }
 
     /// Create account for the receiver of the transaction.
@@ -119,11 +166,19 @@ impl UserTransaction {
 
 /// A user that can sign transactions.  It includes a signer and an account id.
 pub struct UserAccount {
-    runtime: Rc<RefCell<RuntimeStandalone>>,
+    runtime: Arc<RwLock<RuntimeStandalone>>,
     pub account_id: AccountId,
     pub signer: InMemorySigner,
 }
 
+// Users, results and steppers can be moved to other threads, e.g. by the fuzzer.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<UserAccount>();
+    assert_send_sync::<ExecutionResult>();
+    assert_send_sync::<TransactionStepper>();
+};
+
 impl Debug for UserAccount {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UserAccount").field("account_id", &self.account_id).finish()
@@ -133,11 +188,11 @@ impl Debug for UserAccount {
 impl UserAccount {
     #[doc(hidden)]
     pub fn new(
-        runtime: &Rc<RefCell<RuntimeStandalone>>,
+        runtime: &Arc<RwLock<RuntimeStandalone>>,
         account_id: AccountId,
         signer: InMemorySigner,
     ) -> Self {
-        let runtime = Rc::clone(runtime);
+        let runtime = Arc::clone(runtime);
         Self { runtime, account_id, signer }
     }
 
@@ -147,7 +202,7 @@ impl UserAccount {
     }
     /// Look up the account information on chain.
     pub fn account(&self) -> Option<Account> {
-        (*self.runtime).borrow().view_account(self.account_id.as_str())
+        read_runtime(&self.runtime).view_account(self.account_id.as_str())
     }
     /// Transfer yoctoNear to another account
     pub fn transfer(&self, to: AccountId, deposit: Balance) -> ExecutionResult {
@@ -245,12 +300,8 @@ impl UserAccount {
     }
 
     fn transaction(&self, receiver_id: AccountId) -> Transaction {
-        let nonce = (*self.runtime)
-            .borrow()
-            .view_access_key(self.account_id.as_str(), &self.signer.public_key())
-            .unwrap()
-            .nonce
-            + 1;
+        // Transactions signed with a key which doesn't exist fail when they are executed.
+        let nonce = self.access_key().map_or(0, |access_key| access_key.nonce) + 1;
         Transaction::new(
             String::from(self.account_id()),
             self.signer.public_key(),
@@ -260,17 +311,78 @@ impl UserAccount {
         )
     }
 
+    /// Looks up the access key of the signer on chain, e.g. to check the remaining allowance.
+    pub fn access_key(&self) -> Option<AccessKey> {
+        read_runtime(&self.runtime)
+            .view_access_key(self.account_id.as_str(), &self.signer.public_key())
+    }
+
+    /// Returns this user signing transactions with another key. The key has to be added to the
+    /// account for the transactions to succeed.
+    pub fn with_signer(&self, signer: InMemorySigner) -> UserAccount {
+        UserAccount::new(&self.runtime, self.account_id(), signer)
+    }
+
+    /// Adds a new key of the given type to the account and returns this user signing with it.
+    /// Transactions which the key is not allowed to sign, e.g. calls of other methods with a
+    /// function call access key, fail with an `InvalidTxError`, like on a real network.
+    ///
+    /// # Examples
+    /// ```
+    /// use near_sdk_sim::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
+    /// use near_sdk_sim::near_crypto::KeyType;
+    /// use near_sdk_sim::to_yocto;
+    ///
+    /// let root = near_sdk_sim::init_simulator(None);
+    /// let alice = root.create_user("alice".parse().unwrap(), to_yocto("100"));
+    /// let permission = FunctionCallPermission {
+    ///     allowance: Some(to_yocto("1")),
+    ///     receiver_id: "app".to_string(),
+    ///     method_names: vec!["vote".to_string()],
+    /// };
+    /// let access_key =
+    ///     AccessKey { nonce: 0, permission: AccessKeyPermission::FunctionCall(permission) };
+    /// let app_key = alice.add_access_key_signer(KeyType::SECP256K1, access_key);
+    /// assert!(!app_key.transfer(root.account_id(), to_yocto("1")).is_ok());
+    /// ```
+    pub fn add_access_key_signer(&self, key_type: KeyType, access_key: AccessKey) -> UserAccount {
+        let signer = self.new_signer(key_type);
+        self.submit_transaction(
+            self.transaction(self.account_id()).add_key(signer.public_key(), access_key),
+        )
+        .assert_success();
+        self.with_signer(signer)
+    }
+
+    /// Replaces the key of the signer with a new full access key of the given type and returns
+    /// this user signing with the new key.
+    pub fn rotate_key(&self, key_type: KeyType) -> UserAccount {
+        let signer = self.new_signer(key_type);
+        self.submit_transaction(
+            self.transaction(self.account_id())
+                .add_key(signer.public_key(), AccessKey::full_access())
+                .delete_key(self.signer.public_key()),
+        )
+        .assert_success();
+        self.with_signer(signer)
+    }
+
+    fn new_signer(&self, key_type: KeyType) -> InMemorySigner {
+        // The nonce of the current key changes with every transaction, which makes the seed unique.
+        let nonce = self.access_key().map_or(0, |access_key| access_key.nonce);
+        let seed = format!("{} {}", self.signer.public_key(), nonce);
+        InMemorySigner::from_seed(self.account_id.as_str(), key_type, &seed)
+    }
+
     /// Create a user transaction to `receiver_id` to be signed the current user
     pub fn create_transaction(&self, receiver_id: AccountId) -> UserTransaction {
         let transaction = self.transaction(receiver_id);
-        let runtime = Rc::clone(&self.runtime);
+        let runtime = Arc::clone(&self.runtime);
         UserTransaction { transaction, signer: self.signer.clone(), runtime }
     }
 
     fn submit_transaction(&self, transaction: Transaction) -> ExecutionResult {
-        let res = (*self.runtime).borrow_mut().resolve_tx(transaction.sign(&self.signer)).unwrap();
-        (*self.runtime).borrow_mut().process_all().unwrap();
-        outcome_into_result(res, &self.runtime)
+        execute(&self.runtime, transaction.sign(&self.signer))
     }
 
     /// Call a view method on a contract.
@@ -280,7 +392,7 @@ impl UserAccount {
     }
 
     pub fn view(&self, receiver_id: AccountId, method: &str, args: &[u8]) -> ViewResult {
-        (*self.runtime).borrow().view_method_call(receiver_id.as_str(), method, args)
+        read_runtime(&self.runtime).view_method_call(receiver_id.as_str(), method, args)
     }
 
     /// Creates a user and is signed by the `signer_user`
@@ -309,7 +421,8 @@ impl UserAccount {
         self.create_user_from(&self, account_id, amount)
     }
 
-    /// Returns a reference to a memory location of the standalone runtime.
+    /// Returns a reference to a memory location of the standalone runtime. Panics if the runtime is
+    /// borrowed mutably, instead of waiting for the guard to be dropped.
     ///
     /// # Examples
     /// ```
@@ -319,11 +432,12 @@ impl UserAccount {
     /// // with use
     /// let _block = runtime.current_block();
     /// ```
-    pub fn borrow_runtime(&self) -> Ref<RuntimeStandalone> {
-        (*self.runtime).borrow()
+    pub fn borrow_runtime(&self) -> RwLockReadGuard<RuntimeStandalone> {
+        read_runtime(&self.runtime)
     }
 
-    /// Returns a mutable memory location to the standalone runtime.
+    /// Returns a mutable memory location to the standalone runtime. Panics if the runtime is
+    /// already borrowed, instead of waiting for the guard to be dropped.
     ///
     /// # Examples
     /// ```
@@ -333,8 +447,49 @@ impl UserAccount {
     /// // with use
     /// runtime.produce_block().unwrap();
     /// ```
-    pub fn borrow_runtime_mut(&self) -> RefMut<RuntimeStandalone> {
-        (*self.runtime).borrow_mut()
+    pub fn borrow_runtime_mut(&self) -> RwLockWriteGuard<RuntimeStandalone> {
+        write_runtime(&self.runtime)
+    }
+
+    /// Jumps ahead in time without executing blocks. See [`RuntimeStandalone::fast_forward`].
+    pub fn fast_forward(&self, duration: Duration) {
+        write_runtime(&self.runtime).fast_forward(duration)
+    }
+
+    /// Sets the timestamp of the current block.
+    pub fn set_block_timestamp(&self, block_timestamp: u64) {
+        write_runtime(&self.runtime).set_block_timestamp(block_timestamp)
+    }
+
+    /// Sets the height of the current block.
+    pub fn set_block_height(&self, block_height: BlockHeight) {
+        write_runtime(&self.runtime).set_block_height(block_height)
+    }
+
+    /// Creates an independent copy of the runtime and returns this user bound to it. Other users
+    /// can be moved to the copy with [`UserAccount::with_runtime_of`].
+    ///
+    /// # Examples
+    /// ```
+    /// use near_sdk_sim::to_yocto;
+    /// let master_account = near_sdk_sim::init_simulator(None);
+    /// let alice = master_account.create_user("alice".parse().unwrap(), to_yocto("100"));
+    ///
+    /// let fork = master_account.fork();
+    /// let forked_alice = alice.with_runtime_of(&fork);
+    /// forked_alice.transfer(fork.account_id(), to_yocto("50")).assert_success();
+    ///
+    /// assert!(alice.account().unwrap().amount > to_yocto("99"));
+    /// assert!(forked_alice.account().unwrap().amount < to_yocto("51"));
+    /// ```
+    pub fn fork(&self) -> UserAccount {
+        let runtime = read_runtime(&self.runtime).fork();
+        UserAccount::new(&Arc::new(RwLock::new(runtime)), self.account_id(), self.signer.clone())
+    }
+
+    /// Returns this user bound to the runtime of `other`, e.g. a fork.
+    pub fn with_runtime_of(&self, other: &UserAccount) -> UserAccount {
+        UserAccount::new(&other.runtime, self.account_id(), self.signer.clone())
     }
 }
 
@@ -358,7 +513,7 @@ impl<T> ContractAccount<T> {
 /// It returns the `master_account` which can then create accounts and deploy contracts.
 pub fn init_simulator(genesis_config: Option<GenesisConfig>) -> UserAccount {
     let (runtime, signer, root_account_id) = init_runtime(genesis_config);
-    UserAccount::new(&Rc::new(RefCell::new(runtime)), root_account_id, signer)
+    UserAccount::new(&Arc::new(RwLock::new(runtime)), root_account_id, signer)
 }
 
 /// Deploys a contract. Will either deploy or deploy and initialize a contract.
@@ -554,3 +709,107 @@ macro_rules! view {
         (&$contract).user_account.view_method_call((&$contract).contract.$method($($arg),*))
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{AccessKeyPermission, FunctionCallPermission};
+    use crate::errors::{InvalidAccessKeyError, InvalidTxError, TxExecutionError};
+    use crate::transaction::ExecutionStatus;
+
+    fn invalid_tx_error(res: &ExecutionResult) -> InvalidTxError {
+        match res.status() {
+            ExecutionStatus::Failure(TxExecutionError::InvalidTxError(err)) => err,
+            status => panic!("Expected an invalid transaction, got {:?}", status),
+        }
+    }
+
+    #[test]
+    fn function_call_access_key() {
+        let root = init_simulator(None);
+        let status = root.deploy(
+            include_bytes!("../../examples/status-message/res/status_message.wasm").as_ref(),
+            "status".parse().unwrap(),
+            to_yocto("35"),
+        );
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("100"));
+        let permission = FunctionCallPermission {
+            allowance: Some(to_yocto("1")),
+            receiver_id: "status".to_string(),
+            method_names: vec!["set_status".to_string()],
+        };
+        let access_key =
+            AccessKey { nonce: 0, permission: AccessKeyPermission::FunctionCall(permission) };
+        let app_key = alice.add_access_key_signer(KeyType::ED25519, access_key);
+
+        app_key
+            .call(status.account_id(), "set_status", br#"{"message": "hi"}"#, DEFAULT_GAS, 0)
+            .assert_success();
+        let allowance = match app_key.access_key().unwrap().permission {
+            AccessKeyPermission::FunctionCall(permission) => permission.allowance.unwrap(),
+            AccessKeyPermission::FullAccess => unreachable!(),
+        };
+        assert!(allowance < to_yocto("1"));
+
+        let res = app_key.call(status.account_id(), "get_status", b"{}", DEFAULT_GAS, 0);
+        assert!(matches!(
+            invalid_tx_error(&res),
+            InvalidTxError::InvalidAccessKeyError(InvalidAccessKeyError::MethodNameMismatch { .. })
+        ));
+        let res = app_key.transfer(root.account_id(), 1);
+        assert!(matches!(
+            invalid_tx_error(&res),
+            InvalidTxError::InvalidAccessKeyError(InvalidAccessKeyError::RequiresFullAccess)
+        ));
+    }
+
+    #[test]
+    fn rotate_key_and_nonce() {
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("100"));
+        let new_alice = alice.rotate_key(KeyType::SECP256K1);
+        assert!(matches!(
+            invalid_tx_error(&alice.transfer(root.account_id(), 1)),
+            InvalidTxError::InvalidAccessKeyError(InvalidAccessKeyError::AccessKeyNotFound { .. })
+        ));
+        new_alice.transfer(root.account_id(), 1).assert_success();
+
+        let res = new_alice.create_transaction(root.account_id()).nonce(1).transfer(1).submit();
+        assert!(matches!(invalid_tx_error(&res), InvalidTxError::InvalidNonce { .. }));
+    }
+
+    #[test]
+    fn parallel_runtimes() {
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let root = init_simulator(None);
+                    let user =
+                        root.create_user(format!("user{}", i).parse().unwrap(), to_yocto("10"));
+                    user.transfer(root.account_id(), to_yocto("1")).assert_success();
+                    user.account().unwrap().amount
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap() < to_yocto("9"));
+        }
+
+        // Users can be moved to other threads and keep sharing the runtime.
+        let root = init_simulator(None);
+        let alice = root.create_user("alice".parse().unwrap(), to_yocto("100"));
+        let root_id = root.account_id();
+        std::thread::spawn(move || alice.transfer(root_id, to_yocto("50")).assert_success())
+            .join()
+            .unwrap();
+        let alice = root.borrow_runtime().view_account("alice").unwrap();
+        assert!(alice.amount < to_yocto("50"));
+    }
+    #[test]
+    #[should_panic(expected = "The runtime is already borrowed")]
+    fn call_while_runtime_borrowed() {
+        let root = init_simulator(None);
+        let _runtime = root.borrow_runtime();
+        root.create_user("alice".parse().unwrap(), to_yocto("100"));
+    }
+}
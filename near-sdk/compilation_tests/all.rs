@@ -18,4 +18,12 @@ fn compilation_tests() {
     t.pass("compilation_tests/cond_compilation.rs");
     t.compile_fail("compilation_tests/payable_view.rs");
     t.pass("compilation_tests/borsh_storage_key.rs");
+    t.pass("compilation_tests/state_key.rs");
+    t.pass("compilation_tests/state_key_fallback.rs");
+    t.compile_fail("compilation_tests/state_key_empty.rs");
+    t.compile_fail("compilation_tests/state_key_unknown_argument.rs");
+    t.pass("compilation_tests/callback_result.rs");
+    t.pass("compilation_tests/typed_promise.rs");
+    t.compile_fail("compilation_tests/typed_promise_mismatch.rs");
+    t.compile_fail("compilation_tests/typed_promise_join_all_callback.rs");
 }
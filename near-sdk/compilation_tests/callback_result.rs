@@ -0,0 +1,26 @@
+//! Callback that handles the failure of the promise.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::{near_bindgen, PromiseError};
+
+#[near_bindgen]
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct Incrementer {
+    value: u32,
+}
+
+#[near_bindgen]
+impl Incrementer {
+    #[private]
+    pub fn on_inc(&mut self, #[callback_result] by: Result<u32, PromiseError>) -> bool {
+        match by {
+            Ok(by) => {
+                self.value += by;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,49 @@
+//! Contract states without `#[near_bindgen]` on the type are stored under the default key.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::near_bindgen;
+
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct Incrementer {
+    value: u32,
+}
+
+// Simulator proxies, normally generated by `#[near_bindgen]` on the type.
+pub struct IncrementerContract {
+    pub account_id: near_sdk::AccountId,
+}
+
+pub struct SwitchContract {
+    pub account_id: near_sdk::AccountId,
+}
+
+#[near_bindgen]
+impl Incrementer {
+    pub fn inc(&mut self, by: u32) {
+        self.value += by;
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+enum Switch {
+    On,
+    Off,
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Switch::Off
+    }
+}
+
+#[near_bindgen]
+impl Switch {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            Switch::On => Switch::Off,
+            Switch::Off => Switch::On,
+        };
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+//! Only `state_key` is supported as an argument on structs.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::near_bindgen;
+
+#[near_bindgen(storage = "v2")]
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct Incrementer {
+    value: u32,
+}
+
+fn main() {}
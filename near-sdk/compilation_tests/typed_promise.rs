@@ -0,0 +1,55 @@
+//! Cross-contract calls composed through the typed functions generated by `#[ext_contract]`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, TypedPromise};
+
+#[ext_contract(ext_counter)]
+pub trait Counter {
+    fn get(&self) -> u32;
+    // Doesn't clash with the generated submodule.
+    fn typed_calls(&self) -> u32;
+}
+
+#[ext_contract(ext_self)]
+pub trait Callbacks {
+    fn on_get(&mut self, #[callback] value: u32) -> u32;
+    fn on_get_all(&mut self, #[callback_vec] values: Vec<u32>);
+    fn on_get_three(&mut self, #[callback] a: u32, #[callback] b: u32, #[callback] c: u32) -> u32;
+}
+
+#[near_bindgen]
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct Aggregator {}
+
+const GAS: u64 = 5_000_000_000_000;
+
+#[near_bindgen]
+impl Aggregator {
+    pub fn get(&self, counter: AccountId) -> TypedPromise<u32> {
+        ext_counter::typed_calls::get(&counter, 0, GAS).then_map(ext_self::typed_calls::on_get(
+            &env::current_account_id(),
+            0,
+            GAS,
+        ))
+    }
+
+    pub fn get_all(&self, counters: Vec<AccountId>) {
+        let values = counters.iter().map(|counter| ext_counter::typed_calls::get(counter, 0, GAS));
+        TypedPromise::join_all(values.collect()).then_map(ext_self::typed_calls::on_get_all(
+            &env::current_account_id(),
+            0,
+            GAS,
+        ));
+    }
+
+    pub fn get_three(&self, a: AccountId, b: AccountId, c: AccountId) -> TypedPromise<u32> {
+        TypedPromise::all((
+            ext_counter::typed_calls::get(&a, 0, GAS),
+            ext_counter::typed_calls::get(&b, 0, GAS),
+            ext_counter::typed_calls::get(&c, 0, GAS),
+        ))
+        .then_map(ext_self::typed_calls::on_get_three(&env::current_account_id(), 0, GAS))
+    }
+}
+
+fn main() {}
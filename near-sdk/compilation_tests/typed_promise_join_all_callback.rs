@@ -0,0 +1,37 @@
+//! The results of `TypedPromise::join_all` can only be read through a `#[callback_vec]` argument.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, TypedPromise};
+
+#[ext_contract(ext_token)]
+pub trait Token {
+    fn balance_of(&self, account_id: AccountId) -> u64;
+}
+
+#[ext_contract(ext_self)]
+pub trait Callbacks {
+    fn on_balances(&mut self, #[callback] balances: Vec<u64>);
+}
+
+#[near_bindgen]
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct Contract {}
+
+const GAS: u64 = 5_000_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    pub fn check_all(&self, accounts: Vec<AccountId>) -> TypedPromise<()> {
+        let balances = accounts
+            .into_iter()
+            .map(|id| ext_token::typed_calls::balance_of(id, &"token.near", 0, GAS))
+            .collect();
+        TypedPromise::join_all(balances).then_map(ext_self::typed_calls::on_balances(
+            &env::current_account_id(),
+            0,
+            GAS,
+        ))
+    }
+}
+
+fn main() {}
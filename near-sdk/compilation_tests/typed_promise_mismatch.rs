@@ -0,0 +1,30 @@
+//! A callback that reads a different type than the result of the promise it is attached to.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::{env, ext_contract, near_bindgen, AccountId, TypedPromise};
+
+#[ext_contract(ext_token)]
+pub trait Token {
+    fn balance_of(&self, account_id: AccountId) -> u64;
+}
+
+#[ext_contract(ext_self)]
+pub trait Callbacks {
+    fn on_balance(&mut self, #[callback] balance: String) -> bool;
+}
+
+#[near_bindgen]
+#[derive(Default, BorshDeserialize, BorshSerialize)]
+struct Contract {}
+
+const GAS: u64 = 5_000_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    pub fn check(&self, account_id: AccountId) -> TypedPromise<bool> {
+        ext_token::typed_calls::balance_of(account_id, &"token.near", 0, GAS)
+            .then_map(ext_self::typed_calls::on_balance(&env::current_account_id(), 0, GAS))
+    }
+}
+
+fn main() {}
@@ -0,0 +1,58 @@
+//! Minimal bump allocator enabled with the `bump_alloc` feature.
+//!
+//! Memory is handed out sequentially from the end of the initial linear memory, and grown with
+//! `memory.grow` when needed. Deallocation is a no-op, which is fine for contract calls since the
+//! whole instance is dropped after the call, but it means that contracts allocating and freeing a
+//! lot of memory within one call should keep using `wee_alloc`.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::arch::wasm32;
+use core::cell::UnsafeCell;
+
+const PAGE_SIZE: usize = 64 * 1024;
+
+/// Global allocator that never frees memory. Set up with:
+///
+/// ```toml
+/// near-sdk = { version = "*", default-features = false, features = ["bump_alloc"] }
+/// ```
+pub struct BumpAllocator {
+    /// Next free address, or `0` before the first allocation.
+    next: UnsafeCell<usize>,
+}
+
+// Wasm contracts are single-threaded.
+unsafe impl Sync for BumpAllocator {}
+
+impl BumpAllocator {
+    pub const INIT: Self = Self { next: UnsafeCell::new(0) };
+}
+
+unsafe impl GlobalAlloc for BumpAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let next = &mut *self.next.get();
+        if *next == 0 {
+            // Everything before the end of the initial memory is taken by the stack and statics.
+            *next = wasm32::memory_size(0) * PAGE_SIZE;
+        }
+        let start = match next.checked_add(layout.align() - 1) {
+            Some(x) => x & !(layout.align() - 1),
+            None => return core::ptr::null_mut(),
+        };
+        let end = match start.checked_add(layout.size()) {
+            Some(x) => x,
+            None => return core::ptr::null_mut(),
+        };
+        let memory_end = wasm32::memory_size(0) * PAGE_SIZE;
+        if end > memory_end {
+            let pages = (end - memory_end + PAGE_SIZE - 1) / PAGE_SIZE;
+            if wasm32::memory_grow(0, pages) == usize::MAX {
+                return core::ptr::null_mut();
+            }
+        }
+        *next = end;
+        start as *mut u8
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
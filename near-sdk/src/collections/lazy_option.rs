@@ -3,7 +3,8 @@
 //! If the underlying value is large, e.g. the contract needs to store an image, but it doesn't need
 //! to have access to this image at regular calls, then the contract can wrap this image into
 //! `LazyOption` and it will not be deserialized until requested.
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -1,8 +1,9 @@
 //! Legacy `TreeMap` implementation that is using `UnorderedMap`.
 //! DEPRECATED. This implementation is deprecated and may be removed in the future.
 
+use alloc::vec::Vec;
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::ops::Bound;
+use core::ops::Bound;
 
 use crate::collections::UnorderedMap;
 use crate::collections::{append, Vector};
@@ -317,7 +318,7 @@ where
         let lft = node.lft.and_then(|id| self.node(id).map(|n| n.ht)).unwrap_or_default();
         let rgt = node.rgt.and_then(|id| self.node(id).map(|n| n.ht)).unwrap_or_default();
 
-        node.ht = 1 + std::cmp::max(lft, rgt);
+        node.ht = 1 + core::cmp::max(lft, rgt);
         self.save(&node);
     }
 
@@ -679,11 +680,10 @@ mod tests {
 
     extern crate rand;
     use self::rand::RngCore;
+    use core::fmt::{Debug, Formatter, Result};
     use quickcheck::QuickCheck;
-    use serde::export::Formatter;
     use std::collections::BTreeMap;
     use std::collections::HashSet;
-    use std::fmt::{Debug, Result};
 
     /// Return height of the tree - number of nodes on the longest path starting from the root node.
     fn height<K, V>(tree: &LegacyTreeMap<K, V>) -> u64
@@ -1,7 +1,8 @@
 //! A persistent map without iterators. Unlike `near_sdk::collections::UnorderedMap` this map
 //! doesn't store keys and values separately in vectors, so it can't iterate over keys. But it
 //! makes this map more efficient in the number of reads and writes.
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
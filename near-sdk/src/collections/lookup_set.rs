@@ -1,7 +1,8 @@
 //! A persistent set without iterators. Unlike `near_sdk::collections::LookupSet` this set
 //! doesn't store values separately in a vector, so it can't iterate over the values. But it
 //! makes this implementation more efficient in the number of reads and writes.
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -1,5 +1,6 @@
+use alloc::vec::Vec;
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::ops::Bound;
+use core::ops::Bound;
 
 use crate::collections::LookupMap;
 use crate::collections::{append, Vector};
@@ -316,7 +317,7 @@ where
         let lft = node.lft.and_then(|id| self.node(id).map(|n| n.ht)).unwrap_or_default();
         let rgt = node.rgt.and_then(|id| self.node(id).map(|n| n.ht)).unwrap_or_default();
 
-        node.ht = 1 + std::cmp::max(lft, rgt);
+        node.ht = 1 + core::cmp::max(lft, rgt);
         self.save(&node);
     }
 
@@ -647,7 +648,7 @@ where
     }
 }
 
-impl<K, V> std::iter::FusedIterator for Cursor<'_, K, V>
+impl<K, V> core::iter::FusedIterator for Cursor<'_, K, V>
 where
     K: Ord + Clone + BorshSerialize + BorshDeserialize,
     V: BorshSerialize + BorshDeserialize,
@@ -729,11 +730,10 @@ mod tests {
 
     extern crate rand;
     use self::rand::RngCore;
+    use core::fmt::{Debug, Formatter, Result};
     use quickcheck::QuickCheck;
-    use serde::export::Formatter;
     use std::collections::BTreeMap;
     use std::collections::HashSet;
-    use std::fmt::{Debug, Result};
 
     /// Return height of the tree - number of nodes on the longest path starting from the root node.
     fn height<K, V>(tree: &TreeMap<K, V>) -> u64
@@ -2,8 +2,9 @@
 //! hashed but are instead serialized.
 use crate::collections::{append, append_slice, Vector};
 use crate::{env, IntoStorageKey};
+use alloc::vec::Vec;
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::mem::size_of;
+use core::mem::size_of;
 
 const ERR_INCONSISTENT_STATE: &[u8] = b"The collection is an inconsistent state. Did previous smart contract execution terminate unexpectedly?";
 const ERR_KEY_SERIALIZATION: &[u8] = b"Cannot serialize key with Borsh";
@@ -202,7 +203,7 @@ where
     }
 
     /// Copies elements into an `std::vec::Vec`.
-    pub fn to_vec(&self) -> std::vec::Vec<(K, V)> {
+    pub fn to_vec(&self) -> Vec<(K, V)> {
         self.iter().collect()
     }
 
@@ -245,10 +246,10 @@ where
 mod tests {
     use crate::collections::UnorderedMap;
     use crate::test_utils::test_env;
+    use core::iter::FromIterator;
     use rand::seq::SliceRandom;
     use rand::{Rng, SeedableRng};
     use std::collections::{HashMap, HashSet};
-    use std::iter::FromIterator;
 
     #[test]
     pub fn test_insert() {
@@ -2,8 +2,9 @@
 //! hashed but are instead serialized.
 use crate::collections::{append, append_slice, Vector};
 use crate::{env, IntoStorageKey};
+use alloc::vec::Vec;
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::mem::size_of;
+use core::mem::size_of;
 
 const ERR_INCONSISTENT_STATE: &[u8] = b"The collection is an inconsistent state. Did previous smart contract execution terminate unexpectedly?";
 const ERR_ELEMENT_SERIALIZATION: &[u8] = b"Cannot serialize element with Borsh";
@@ -148,7 +149,7 @@ where
     }
 
     /// Copies elements into an `std::vec::Vec`.
-    pub fn to_vec(&self) -> std::vec::Vec<T> {
+    pub fn to_vec(&self) -> Vec<T> {
         self.iter().collect()
     }
 
@@ -175,10 +176,10 @@ where
 mod tests {
     use crate::collections::UnorderedSet;
     use crate::test_utils::test_env;
+    use core::iter::FromIterator;
     use rand::seq::SliceRandom;
     use rand::{Rng, SeedableRng};
     use std::collections::HashSet;
-    use std::iter::FromIterator;
 
     #[test]
     pub fn test_insert() {
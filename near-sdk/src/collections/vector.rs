@@ -1,6 +1,7 @@
 //! A vector implemented on a trie. Unlike standard vector does not support insertion and removal
 //! of an element results in the last element being placed in the empty position.
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
@@ -230,8 +231,8 @@ where
 }
 
 #[cfg(feature = "expensive-debug")]
-impl<T: std::fmt::Debug + BorshDeserialize> std::fmt::Debug for Vector<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: core::fmt::Debug + BorshDeserialize> core::fmt::Debug for Vector<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.to_vec().fmt(f)
     }
 }
@@ -3,23 +3,30 @@
 //! whenever possible. In case of cross-contract calls prefer using even higher-level API available
 //! through `callback_args`, `callback_args_vec`, `ext_contract`, `Promise`, and `PromiseOrValue`.
 
-use std::borrow::Borrow;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
 #[cfg(not(target_arch = "wasm32"))]
-use std::cell::RefCell;
-use std::convert::TryFrom;
-use std::mem::size_of;
+use core::cell::RefCell;
+use core::convert::TryFrom;
+use core::mem::size_of;
+#[cfg(not(target_arch = "wasm32"))]
+use std::boxed::Box;
+#[cfg(feature = "std")]
 use std::panic as std_panic;
 
 use super::sys;
 use crate::types::{
-    AccountId, Balance, BlockHeight, Gas, PromiseIndex, PromiseResult, PublicKey, StorageUsage,
+    AccountId, Balance, BlockHeight, Gas, PromiseError, PromiseIndex, PromiseResult, PublicKey,
+    StorageUsage,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::environment::blockchain_interface::BlockchainInterface;
 
 #[cfg(not(target_arch = "wasm32"))]
-thread_local! {
+std::thread_local! {
 /// Low-level blockchain interface wrapped by the environment. Prefer using `env::*` and `testing_env`
 /// for interacting with the real and fake blockchains.
     pub static BLOCKCHAIN_INTERFACE: RefCell<Option<Box<dyn BlockchainInterface>>>
@@ -34,10 +41,12 @@ const REGISTER_EXPECTED_ERR: &str =
 /// guest code is not parallel.
 const ATOMIC_OP_REGISTER: u64 = 0;
 /// Register used to record evicted values from the storage.
-const EVICTED_REGISTER: u64 = std::u64::MAX - 1;
+const EVICTED_REGISTER: u64 = core::u64::MAX - 1;
 
-/// Key used to store the state of the contract.
-const STATE_KEY: &[u8] = b"STATE";
+/// Default key used to store the state of the contract, see [`ContractState`].
+///
+/// [`ContractState`]: crate::ContractState
+pub const STATE_KEY: &[u8] = b"STATE";
 
 /// The minimum length of a valid account ID.
 const MIN_ACCOUNT_ID_LEN: u64 = 2;
@@ -110,15 +119,30 @@ pub fn take_blockchain_interface() -> Option<Box<dyn BlockchainInterface>> {
 
 /// Implements panic hook that converts `PanicInfo` into a string and provides it through the
 /// blockchain interface.
+#[cfg(feature = "std")]
 fn panic_hook_impl(info: &std_panic::PanicInfo) {
     panic(info.to_string().as_bytes());
 }
 
 /// Setups panic hook to expose error info to the blockchain.
+#[cfg(feature = "std")]
 pub fn setup_panic_hook() {
     std_panic::set_hook(Box::new(panic_hook_impl));
 }
 
+/// Without `std` the panic handler below already exposes error info to the blockchain.
+#[cfg(not(feature = "std"))]
+pub fn setup_panic_hook() {}
+
+/// Panic handler of `no_std` contracts, provides the panic message through the blockchain
+/// interface.
+#[cfg(all(not(feature = "std"), target_arch = "wasm32"))]
+#[panic_handler]
+fn panic_handler(info: &core::panic::PanicInfo) -> ! {
+    use alloc::string::ToString;
+    panic(info.to_string().as_bytes())
+}
+
 /// Reads the content of the `register_id`. If register is not used returns `None`.
 pub fn read_register(register_id: u64) -> Option<Vec<u8>> {
     let len = register_len(register_id)?;
@@ -130,7 +154,7 @@ pub fn read_register(register_id: u64) -> Option<Vec<u8>> {
 /// Returns the size of the register. If register is not used returns `None`.
 pub fn register_len(register_id: u64) -> Option<u64> {
     let len = unsafe { sys::register_len(register_id) };
-    if len == std::u64::MAX {
+    if len == core::u64::MAX {
         None
     } else {
         Some(len)
@@ -471,6 +495,25 @@ pub fn promise_result(result_idx: u64) -> PromiseResult {
         _ => unreachable!(),
     }
 }
+/// Deserializes the result of the promise under `result_idx` from JSON. Returns an error instead of
+/// panicking if the promise has failed or its result can't be deserialized, so that the callback
+/// can handle it, e.g. refund a deposit.
+#[cfg(feature = "json")]
+pub fn promise_result_as<T: serde::de::DeserializeOwned>(
+    result_idx: u64,
+) -> Result<T, PromiseError> {
+    promise_result(result_idx)
+        .into_result()
+        .and_then(|data| serde_json::from_slice(&data).map_err(|_| PromiseError::Deserialization))
+}
+/// Same as `promise_result_as` but deserializes the result from Borsh.
+pub fn promise_result_as_borsh<T: borsh::BorshDeserialize>(
+    result_idx: u64,
+) -> Result<T, PromiseError> {
+    promise_result(result_idx)
+        .into_result()
+        .and_then(|data| T::try_from_slice(&data).map_err(|_| PromiseError::Deserialization))
+}
 /// Consider the execution result of promise under `promise_idx` as execution result of this
 /// function.
 pub fn promise_return(promise_idx: PromiseIndex) {
@@ -507,13 +550,25 @@ pub fn value_return(value: &[u8]) {
 }
 /// Terminates the execution of the program with the UTF-8 encoded message.
 pub fn panic(message: &[u8]) -> ! {
-    unsafe { sys::panic_utf8(message.len() as _, message.as_ptr() as _) }
+    // Unwinding out of the `extern "C"` functions of the mocked blockchain aborts the process, so
+    // in unit tests the blockchain interface is called directly and the panic can be caught.
+    #[cfg(not(target_arch = "wasm32"))]
+    BLOCKCHAIN_INTERFACE.with(|b| unsafe {
+        b.borrow()
+            .as_ref()
+            .expect("Blockchain interface not set.")
+            .panic_utf8(message.len() as _, message.as_ptr() as _)
+    });
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        sys::panic_utf8(message.len() as _, message.as_ptr() as _)
+    }
     unreachable!()
 }
 /// Logs the string message message. This message is stored on chain.
 pub fn log_str(message: &str) {
     #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
-    eprintln!("{}", message);
+    std::eprintln!("{}", message);
 
     unsafe { sys::log_utf8(message.len() as _, message.as_ptr() as _) }
 }
@@ -522,7 +577,7 @@ pub fn log_str(message: &str) {
 #[deprecated(since = "4.0.0", note = "Use env::log_str for logging messages.")]
 pub fn log(message: &[u8]) {
     #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
-    eprintln!("{}", String::from_utf8_lossy(message));
+    std::eprintln!("{}", String::from_utf8_lossy(message));
 
     unsafe { sys::log_utf8(message.len() as _, message.as_ptr() as _) }
 }
@@ -582,18 +637,34 @@ pub fn storage_has_key(key: &[u8]) -> bool {
 // ############################################
 /// Load the state of the given object.
 pub fn state_read<T: borsh::BorshDeserialize>() -> Option<T> {
-    storage_read(STATE_KEY)
-        .map(|data| T::try_from_slice(&data).expect("Cannot deserialize the contract state."))
+    state_read_key(STATE_KEY)
 }
 
 pub fn state_write<T: borsh::BorshSerialize>(state: &T) {
-    let data = state.try_to_vec().expect("Cannot serialize the contract state.");
-    storage_write(STATE_KEY, &data);
+    state_write_key(STATE_KEY, state)
 }
 
 /// Returns `true` if the contract state exists and `false` otherwise.
 pub fn state_exists() -> bool {
-    storage_has_key(STATE_KEY)
+    state_exists_key(STATE_KEY)
+}
+
+/// Load the state of the given object stored under a custom key. Allows splitting the contract
+/// state into sections that are loaded independently, or using a root key other than `STATE`.
+pub fn state_read_key<T: borsh::BorshDeserialize>(key: &[u8]) -> Option<T> {
+    storage_read(key)
+        .map(|data| T::try_from_slice(&data).expect("Cannot deserialize the contract state."))
+}
+
+/// Write the state of the given object under a custom key.
+pub fn state_write_key<T: borsh::BorshSerialize>(key: &[u8], state: &T) {
+    let data = state.try_to_vec().expect("Cannot serialize the contract state.");
+    storage_write(key, &data);
+}
+
+/// Returns `true` if the state stored under the given key exists and `false` otherwise.
+pub fn state_exists_key(key: &[u8]) -> bool {
+    storage_has_key(key)
 }
 
 // #####################################
@@ -729,4 +800,43 @@ mod tests {
         assert!(!is_valid_account_id(&[0, 1, 2]));
         assert!(is_valid_account_id(b"near"));
     }
+
+    #[test]
+    fn test_state_key() {
+        crate::test_utils::test_env::setup();
+        assert!(!state_exists());
+        state_write_key(b"config", &42u64);
+        assert!(state_exists_key(b"config"));
+        assert!(!state_exists());
+        assert_eq!(state_read_key::<u64>(b"config"), Some(42));
+
+        state_write(&7u32);
+        assert_eq!(state_read_key::<u32>(STATE_KEY), Some(7));
+        assert_eq!(state_read::<u32>(), Some(7));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_promise_result_as() {
+        use crate::test_utils::VMContextBuilder;
+        use crate::testing_env;
+        use borsh::BorshSerialize;
+
+        testing_env!(
+            VMContextBuilder::new().build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful(b"\"hello\"".to_vec()),
+                PromiseResult::Successful(5u32.try_to_vec().unwrap()),
+                PromiseResult::Failed,
+            ],
+        );
+        assert_eq!(promise_result_as::<String>(0), Ok("hello".to_string()));
+        assert_eq!(promise_result_as::<u32>(0), Err(PromiseError::Deserialization));
+        assert_eq!(promise_result_as_borsh::<u32>(1), Ok(5));
+        assert_eq!(promise_result_as_borsh::<u64>(1), Err(PromiseError::Deserialization));
+        assert_eq!(promise_result_as_borsh::<u32>(2), Err(PromiseError::Failed));
+    }
 }
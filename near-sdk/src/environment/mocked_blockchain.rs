@@ -1,13 +1,18 @@
 use crate::environment::blockchain_interface::BlockchainInterface;
-use crate::test_utils::VMContextBuilder;
-use crate::types::{Balance, PromiseResult};
+use crate::test_utils::receipts::ReceiptRecorder;
+use crate::test_utils::{MockedAction, MockedFunctionCall, MockedReceipt, VMContextBuilder};
+use crate::types::{AccountId, Balance, PromiseResult};
 use crate::RuntimeFeesConfig;
 use near_vm_logic::mocks::mock_external::{MockedExternal, Receipt};
 use near_vm_logic::mocks::mock_memory::MockedMemory;
 use near_vm_logic::types::PromiseResult as VmPromiseResult;
 use near_vm_logic::{External, MemoryLike, VMConfig, VMContext, VMLogic, VMOutcome};
+use std::boxed::Box;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
 
 /// Mocked blockchain that can be used in the tests for the smart contracts.
 /// It implements `BlockchainInterface` by redirecting calls to `VMLogic`. It unwraps errors of
@@ -19,6 +24,8 @@ pub struct MockedBlockchain {
     // We keep ownership over logic fixture so that references in `VMLogic` are valid.
     #[allow(dead_code)]
     logic_fixture: LogicFixture,
+    /// Only set with the default memory, where pointers passed by the contract can be read directly.
+    receipts: Option<RefCell<ReceiptRecorder>>,
 }
 
 impl Default for MockedBlockchain {
@@ -54,6 +61,7 @@ impl MockedBlockchain {
         validators: HashMap<String, Balance>,
         memory_opt: Option<Box<dyn MemoryLike>>,
     ) -> Self {
+        let receipts = if memory_opt.is_none() { Some(Default::default()) } else { None };
         let mut ext = Box::new(MockedExternal::new());
         ext.fake_trie = storage;
         ext.validators = validators;
@@ -78,16 +86,32 @@ impl MockedBlockchain {
         };
 
         let logic = RefCell::new(logic);
-        Self { logic, logic_fixture }
+        Self { logic, logic_fixture, receipts }
     }
 
     pub fn take_storage(&mut self) -> HashMap<Vec<u8>, Vec<u8>> {
         std::mem::take(&mut self.logic_fixture.ext.fake_trie)
     }
 
+    pub(crate) fn restore_storage(&mut self, storage: HashMap<Vec<u8>, Vec<u8>>) {
+        self.logic_fixture.ext.fake_trie = storage;
+    }
+
     pub fn created_receipts(&self) -> &Vec<Receipt> {
         self.logic_fixture.ext.get_receipt_create_calls()
     }
+
+    /// Receipts created by the contract, with the arguments of function calls kept as raw bytes.
+    /// Not available if the blockchain was created with a custom memory.
+    pub fn mocked_receipts(&self) -> Option<Vec<MockedReceipt>> {
+        self.receipts.as_ref().map(|receipts| receipts.borrow().receipts().to_vec())
+    }
+
+    fn record<F: FnOnce(&mut ReceiptRecorder)>(&self, f: F) {
+        if let Some(receipts) = &self.receipts {
+            f(&mut receipts.borrow_mut())
+        }
+    }
     pub fn outcome(&self) -> VMOutcome {
         self.logic.borrow().clone_outcome()
     }
@@ -101,6 +125,34 @@ impl MockedBlockchain {
     }
 }
 
+unsafe fn read_bytes(len: u64, ptr: u64) -> Vec<u8> {
+    std::slice::from_raw_parts(ptr as *const u8, len as usize).to_vec()
+}
+
+unsafe fn read_account_id(len: u64, ptr: u64) -> AccountId {
+    AccountId::new_unchecked(String::from_utf8(read_bytes(len, ptr)).unwrap())
+}
+
+unsafe fn read_balance(ptr: u64) -> Balance {
+    std::ptr::read_unaligned(ptr as *const Balance)
+}
+
+unsafe fn read_function_call(
+    method_name_len: u64,
+    method_name_ptr: u64,
+    arguments_len: u64,
+    arguments_ptr: u64,
+    amount_ptr: u64,
+    gas: u64,
+) -> MockedAction {
+    MockedAction::FunctionCall(MockedFunctionCall {
+        method_name: String::from_utf8(read_bytes(method_name_len, method_name_ptr)).unwrap(),
+        args: read_bytes(arguments_len, arguments_ptr),
+        gas,
+        deposit: read_balance(amount_ptr),
+    })
+}
+
 impl BlockchainInterface for MockedBlockchain {
     unsafe fn read_register(&self, register_id: u64, ptr: u64) {
         self.logic.borrow_mut().read_register(register_id, ptr).unwrap()
@@ -217,7 +269,8 @@ impl BlockchainInterface for MockedBlockchain {
         amount_ptr: u64,
         gas: u64,
     ) -> u64 {
-        self.logic
+        let promise_index = self
+            .logic
             .borrow_mut()
             .promise_create(
                 account_id_len,
@@ -229,7 +282,22 @@ impl BlockchainInterface for MockedBlockchain {
                 amount_ptr,
                 gas,
             )
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.create(read_account_id(account_id_len, account_id_ptr), None);
+            receipts.add_action(
+                promise_index,
+                read_function_call(
+                    method_name_len,
+                    method_name_ptr,
+                    arguments_len,
+                    arguments_ptr,
+                    amount_ptr,
+                    gas,
+                ),
+            );
+        });
+        promise_index
     }
 
     unsafe fn promise_then(
@@ -244,7 +312,8 @@ impl BlockchainInterface for MockedBlockchain {
         amount_ptr: u64,
         gas: u64,
     ) -> u64 {
-        self.logic
+        let new_promise_index = self
+            .logic
             .borrow_mut()
             .promise_then(
                 promise_index,
@@ -257,15 +326,43 @@ impl BlockchainInterface for MockedBlockchain {
                 amount_ptr,
                 gas,
             )
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.create(read_account_id(account_id_len, account_id_ptr), Some(promise_index));
+            receipts.add_action(
+                new_promise_index,
+                read_function_call(
+                    method_name_len,
+                    method_name_ptr,
+                    arguments_len,
+                    arguments_ptr,
+                    amount_ptr,
+                    gas,
+                ),
+            );
+        });
+        new_promise_index
     }
 
     unsafe fn promise_and(&self, promise_idx_ptr: u64, promise_idx_count: u64) -> u64 {
-        self.logic.borrow_mut().promise_and(promise_idx_ptr, promise_idx_count).unwrap()
+        let promise_index =
+            self.logic.borrow_mut().promise_and(promise_idx_ptr, promise_idx_count).unwrap();
+        self.record(|receipts| {
+            receipts.and(std::slice::from_raw_parts(
+                promise_idx_ptr as *const u64,
+                promise_idx_count as usize,
+            ))
+        });
+        promise_index
     }
 
     unsafe fn promise_batch_create(&self, account_id_len: u64, account_id_ptr: u64) -> u64 {
-        self.logic.borrow_mut().promise_batch_create(account_id_len, account_id_ptr).unwrap()
+        let promise_index =
+            self.logic.borrow_mut().promise_batch_create(account_id_len, account_id_ptr).unwrap();
+        self.record(|receipts| {
+            receipts.create(read_account_id(account_id_len, account_id_ptr), None)
+        });
+        promise_index
     }
     unsafe fn promise_batch_then(
         &self,
@@ -273,13 +370,19 @@ impl BlockchainInterface for MockedBlockchain {
         account_id_len: u64,
         account_id_ptr: u64,
     ) -> u64 {
-        self.logic
+        let new_promise_index = self
+            .logic
             .borrow_mut()
             .promise_batch_then(promise_index, account_id_len, account_id_ptr)
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.create(read_account_id(account_id_len, account_id_ptr), Some(promise_index))
+        });
+        new_promise_index
     }
     unsafe fn promise_batch_action_create_account(&self, promise_index: u64) {
-        self.logic.borrow_mut().promise_batch_action_create_account(promise_index).unwrap()
+        self.logic.borrow_mut().promise_batch_action_create_account(promise_index).unwrap();
+        self.record(|receipts| receipts.add_action(promise_index, MockedAction::CreateAccount));
     }
     unsafe fn promise_batch_action_deploy_contract(
         &self,
@@ -290,7 +393,13 @@ impl BlockchainInterface for MockedBlockchain {
         self.logic
             .borrow_mut()
             .promise_batch_action_deploy_contract(promise_index, code_len, code_ptr)
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                MockedAction::DeployContract { code: read_bytes(code_len, code_ptr) },
+            )
+        });
     }
     unsafe fn promise_batch_action_function_call(
         &self,
@@ -313,10 +422,29 @@ impl BlockchainInterface for MockedBlockchain {
                 amount_ptr,
                 gas,
             )
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                read_function_call(
+                    method_name_len,
+                    method_name_ptr,
+                    arguments_len,
+                    arguments_ptr,
+                    amount_ptr,
+                    gas,
+                ),
+            )
+        });
     }
     unsafe fn promise_batch_action_transfer(&self, promise_index: u64, amount_ptr: u64) {
-        self.logic.borrow_mut().promise_batch_action_transfer(promise_index, amount_ptr).unwrap()
+        self.logic.borrow_mut().promise_batch_action_transfer(promise_index, amount_ptr).unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                MockedAction::Transfer { deposit: read_balance(amount_ptr) },
+            )
+        });
     }
     unsafe fn promise_batch_action_stake(
         &self,
@@ -328,7 +456,16 @@ impl BlockchainInterface for MockedBlockchain {
         self.logic
             .borrow_mut()
             .promise_batch_action_stake(promise_index, amount_ptr, public_key_len, public_key_ptr)
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                MockedAction::Stake {
+                    stake: read_balance(amount_ptr),
+                    public_key: read_bytes(public_key_len, public_key_ptr),
+                },
+            )
+        });
     }
     unsafe fn promise_batch_action_add_key_with_full_access(
         &self,
@@ -345,7 +482,16 @@ impl BlockchainInterface for MockedBlockchain {
                 public_key_ptr,
                 nonce,
             )
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                MockedAction::AddKeyWithFullAccess {
+                    public_key: read_bytes(public_key_len, public_key_ptr),
+                    nonce,
+                },
+            )
+        });
     }
     unsafe fn promise_batch_action_add_key_with_function_call(
         &self,
@@ -372,7 +518,25 @@ impl BlockchainInterface for MockedBlockchain {
                 method_names_len,
                 method_names_ptr,
             )
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            let allowance = read_balance(allowance_ptr);
+            let method_names = read_bytes(method_names_len, method_names_ptr)
+                .split(|b| *b == b',')
+                .filter(|name| !name.is_empty())
+                .map(|name| String::from_utf8(name.to_vec()).unwrap())
+                .collect();
+            receipts.add_action(
+                promise_index,
+                MockedAction::AddKeyWithFunctionCall {
+                    public_key: read_bytes(public_key_len, public_key_ptr),
+                    nonce,
+                    allowance: if allowance == 0 { None } else { Some(allowance) },
+                    receiver_id: read_account_id(receiver_id_len, receiver_id_ptr),
+                    method_names,
+                },
+            )
+        });
     }
     unsafe fn promise_batch_action_delete_key(
         &self,
@@ -383,7 +547,13 @@ impl BlockchainInterface for MockedBlockchain {
         self.logic
             .borrow_mut()
             .promise_batch_action_delete_key(promise_index, public_key_len, public_key_ptr)
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                MockedAction::DeleteKey { public_key: read_bytes(public_key_len, public_key_ptr) },
+            )
+        });
     }
     unsafe fn promise_batch_action_delete_account(
         &self,
@@ -398,7 +568,15 @@ impl BlockchainInterface for MockedBlockchain {
                 beneficiary_id_len,
                 beneficiary_id_ptr,
             )
-            .unwrap()
+            .unwrap();
+        self.record(|receipts| {
+            receipts.add_action(
+                promise_index,
+                MockedAction::DeleteAccount {
+                    beneficiary_id: read_account_id(beneficiary_id_len, beneficiary_id_ptr),
+                },
+            )
+        });
     }
     unsafe fn promise_results_count(&self) -> u64 {
         self.logic.borrow_mut().promise_results_count().unwrap()
@@ -37,6 +37,7 @@ extern "C" {
     pub fn value_return(value_len: u64, value_ptr: u64);
     #[allow(dead_code)]
     pub fn panic();
+    #[cfg(target_arch = "wasm32")]
     pub fn panic_utf8(len: u64, ptr: u64);
     pub fn log_utf8(len: u64, ptr: u64);
     #[allow(dead_code)]
@@ -1,9 +1,19 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 #[cfg(test)]
 extern crate quickcheck;
 
+// Also used by the generated code, which can't rely on the prelude of `std` in `no_std` contracts.
+#[doc(hidden)]
+pub extern crate alloc;
+
+// The mocked blockchain used off-chain always needs the standard library.
+#[cfg(all(not(feature = "std"), not(target_arch = "wasm32")))]
+extern crate std;
+
 pub use near_sdk_macros::{
-    callback, callback_vec, ext_contract, init, metadata, near_bindgen, result_serializer,
-    serializer, BorshStorageKey, PanicOnDefault,
+    callback, callback_result, callback_vec, ext_contract, init, metadata, near_bindgen,
+    result_serializer, serializer, BorshStorageKey, PanicOnDefault,
 };
 
 #[cfg(feature = "unstable")]
@@ -19,9 +29,15 @@ pub use environment::sys;
 mod promise;
 pub use promise::{Promise, PromiseOrValue};
 
+mod typed_promise;
+pub use typed_promise::{Joined, TypedCallback, TypedPromise, TypedPromiseTuple};
+
+pub mod rand;
+
 mod metadata;
 pub use metadata::{Metadata, MethodMetadata};
 
+#[cfg(feature = "json")]
 pub mod json_types;
 
 mod types;
@@ -49,19 +65,36 @@ pub mod test_utils;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+#[cfg(all(feature = "bump_alloc", target_arch = "wasm32"))]
+pub mod bump_alloc;
+
+#[cfg(all(feature = "bump_alloc", not(feature = "wee_alloc"), target_arch = "wasm32"))]
+#[global_allocator]
+static ALLOC: bump_alloc::BumpAllocator = bump_alloc::BumpAllocator::INIT;
+
+#[cfg(all(feature = "bump_alloc", feature = "wee_alloc", target_arch = "wasm32"))]
+compile_error!(
+    "Features `bump_alloc` and `wee_alloc` both set a global allocator. \
+     Disable default features to use `bump_alloc`."
+);
+
 // Exporting common crates
 
 #[doc(hidden)]
 pub use borsh;
 
+#[cfg(feature = "json")]
 #[doc(hidden)]
 pub use base64;
 
+#[cfg(feature = "json")]
 #[doc(hidden)]
 pub use bs58;
 
+#[cfg(feature = "json")]
 #[doc(hidden)]
 pub use serde;
 
+#[cfg(feature = "json")]
 #[doc(hidden)]
 pub use serde_json;
@@ -1,3 +1,5 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use borsh::{schema::BorshSchemaContainer, BorshDeserialize, BorshSchema, BorshSerialize};
 /// Version of the metadata format.
 const METADATA_SEMVER: [u32; 3] = [0, 1, 0];
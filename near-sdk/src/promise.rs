@@ -1,11 +1,54 @@
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use borsh::maybestd::collections::HashMap;
+use borsh::maybestd::io::{Error, Write};
 use borsh::BorshSchema;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::io::{Error, Write};
-use std::rc::Rc;
+use core::cell::{Cell, RefCell};
+#[cfg(all(not(feature = "std"), not(target_arch = "wasm32")))]
+use std::thread_local;
+
+use crate::{AccountId, Balance, Gas, GasWeight, PromiseIndex, PublicKey};
+
+/// Default gas kept out of the distribution of unused gas to weighted function calls, to cover the
+/// rest of the execution of the current method.
+const DEFAULT_GAS_RESERVED_FOR_EXECUTION: Gas = 5_000_000_000_000;
+/// Gas kept out of the distribution of unused gas for every scheduled function call, to cover the
+/// fees of sending and executing the function call action.
+const GAS_RESERVED_PER_FUNCTION_CALL: Gas = 5_000_000_000_000;
+/// Gas kept out of the distribution of unused gas for every receipt a scheduled promise waits for,
+/// to cover the fees of the data receipt that passes the result.
+const GAS_RESERVED_PER_DEPENDENCY: Gas = 10_000_000_000_000;
+
+/// Replaces `thread_local!` without `std`. Contracts are executed on a single thread, so a static
+/// is enough.
+#[cfg(all(not(feature = "std"), target_arch = "wasm32"))]
+macro_rules! thread_local {
+    ($($(#[$attr:meta])* static $name:ident: $ty:ty = const { $init:expr };)*) => {
+        $($(#[$attr])* static $name: local_key::LocalKey<$ty> = local_key::LocalKey($init);)*
+    };
+}
+
+#[cfg(all(not(feature = "std"), target_arch = "wasm32"))]
+mod local_key {
+    pub(super) struct LocalKey<T>(pub(super) T);
 
-use crate::{AccountId, Balance, Gas, PromiseIndex, PublicKey};
+    // The contract never spawns threads.
+    unsafe impl<T> Sync for LocalKey<T> {}
 
+    impl<T> LocalKey<T> {
+        pub(super) fn with<R>(&'static self, f: impl FnOnce(&T) -> R) -> R {
+            f(&self.0)
+        }
+    }
+}
+
+thread_local! {
+    /// Gas kept out of the distribution of unused gas, see `Promise::set_gas_reserved_for_execution`.
+    static GAS_RESERVED_FOR_EXECUTION: Cell<Gas> = const { Cell::new(DEFAULT_GAS_RESERVED_FOR_EXECUTION) };
+}
+
+#[non_exhaustive]
 pub enum PromiseAction {
     CreateAccount,
     DeployContract {
@@ -17,6 +60,12 @@ pub enum PromiseAction {
         amount: Balance,
         gas: Gas,
     },
+    FunctionCallWeight {
+        method_name: Vec<u8>,
+        arguments: Vec<u8>,
+        amount: Balance,
+        weight: GasWeight,
+    },
     Transfer {
         amount: Balance,
     },
@@ -60,6 +109,10 @@ impl PromiseAction {
                     *gas,
                 )
             }
+            // `Promise` turns weighted function calls into regular ones before scheduling them.
+            FunctionCallWeight { .. } => crate::env::panic(
+                b"Weighted function calls can only be scheduled through `Promise::function_call_weight`",
+            ),
             Transfer { amount } => {
                 crate::env::promise_batch_action_transfer(promise_index, *amount)
             }
@@ -250,6 +303,44 @@ impl Promise {
         self.add_action(PromiseAction::FunctionCall { method_name, arguments, amount, gas })
     }
 
+    /// A low-level interface for making a function call with a share of the unused prepaid gas.
+    ///
+    /// When the promise is scheduled, i.e. dropped, the prepaid gas that is left after keeping a
+    /// reserve for the rest of the execution, the gas of other function calls and the scheduling
+    /// fees is split between the weighted function calls of the promise and of the promises it is
+    /// combined with through [`Promise::and`] and [`Promise::then`], proportionally to their
+    /// weights. Promises scheduled later only get the gas that is left, so combine the function
+    /// calls that should share the gas into one promise. Panics if there is no gas left to split.
+    ///
+    /// ```
+    /// # use near_sdk::{Promise, GasWeight, testing_env};
+    /// # use near_sdk::test_utils::VMContextBuilder;
+    /// # testing_env!(VMContextBuilder::new().prepaid_gas(300_000_000_000_000).build());
+    /// // `ft_transfer_call` gets three times more gas than the callback.
+    /// Promise::new("token.near".parse().unwrap())
+    ///     .function_call_weight(b"ft_transfer_call".to_vec(), vec![], 1, GasWeight(3))
+    ///     .then(Promise::new("alice.near".parse().unwrap()).function_call_weight(
+    ///         b"on_transfer".to_vec(),
+    ///         vec![],
+    ///         0,
+    ///         GasWeight(1),
+    ///     ));
+    /// ```
+    pub fn function_call_weight(
+        self,
+        method_name: Vec<u8>,
+        arguments: Vec<u8>,
+        amount: Balance,
+        weight: GasWeight,
+    ) -> Self {
+        self.add_action(PromiseAction::FunctionCallWeight {
+            method_name,
+            arguments,
+            amount,
+            weight,
+        })
+    }
+
     /// Transfer tokens to the account that this promise acts on.
     pub fn transfer(self, amount: Balance) -> Self {
         self.add_action(PromiseAction::Transfer { amount })
@@ -390,6 +481,12 @@ impl Promise {
         self
     }
 
+    /// Sets the gas kept out of the distribution of unused gas to weighted function calls, to
+    /// cover the rest of the execution of the current method. Defaults to 5 Tgas.
+    pub fn set_gas_reserved_for_execution(gas: Gas) {
+        GAS_RESERVED_FOR_EXECUTION.with(|reserved| reserved.set(gas));
+    }
+
     fn construct_recursively(&self) -> PromiseIndex {
         let res = match &self.subtype {
             PromiseSubtype::Single(x) => x.construct_recursively(),
@@ -402,12 +499,116 @@ impl Promise {
     }
 }
 
+impl Promise {
+    /// Collects the single promises of this tree that were not scheduled yet, dependencies first.
+    fn collect_unscheduled(&self, singles: &mut Vec<Rc<PromiseSingle>>) {
+        match &self.subtype {
+            PromiseSubtype::Single(x) => {
+                if x.promise_index.borrow().is_some() || singles.iter().any(|s| Rc::ptr_eq(s, x)) {
+                    return;
+                }
+                if let Some(after) = x.after.borrow().as_ref() {
+                    after.collect_unscheduled(singles);
+                }
+                singles.push(Rc::clone(x));
+            }
+            PromiseSubtype::Joint(x) => {
+                if x.promise_index.borrow().is_some() {
+                    return;
+                }
+                x.promise_a.collect_unscheduled(singles);
+                x.promise_b.collect_unscheduled(singles);
+            }
+        }
+    }
+
+    /// Number of receipts that a promise scheduled after this one depends on.
+    fn receipt_count(&self) -> u64 {
+        match &self.subtype {
+            PromiseSubtype::Single(_) => 1,
+            PromiseSubtype::Joint(x) => x.promise_a.receipt_count() + x.promise_b.receipt_count(),
+        }
+    }
+}
+
+/// Splits the unused prepaid gas between the weighted function calls of the given promises, which
+/// are about to be scheduled, turning them into regular function calls.
+fn distribute_unused_gas(singles: &[Rc<PromiseSingle>]) {
+    let mut total_weight: u128 = 0;
+    let mut reserved = GAS_RESERVED_FOR_EXECUTION.with(Cell::get);
+    for single in singles {
+        if let Some(after) = single.after.borrow().as_ref() {
+            reserved = reserved.saturating_add(GAS_RESERVED_PER_DEPENDENCY * after.receipt_count());
+        }
+        for action in single.actions.borrow().iter() {
+            match action {
+                PromiseAction::FunctionCall { gas, .. } => {
+                    reserved = reserved
+                        .saturating_add(*gas)
+                        .saturating_add(GAS_RESERVED_PER_FUNCTION_CALL);
+                }
+                PromiseAction::FunctionCallWeight { weight, .. } => {
+                    total_weight += weight.0 as u128;
+                    reserved = reserved.saturating_add(GAS_RESERVED_PER_FUNCTION_CALL);
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut unused_gas =
+        crate::env::prepaid_gas().saturating_sub(crate::env::used_gas()).saturating_sub(reserved);
+    if unused_gas == 0 && total_weight > 0 {
+        crate::env::panic(b"Not enough prepaid gas left to split between weighted function calls");
+    }
+    for single in singles {
+        for action in single.actions.borrow_mut().iter_mut() {
+            if let PromiseAction::FunctionCallWeight { method_name, arguments, amount, weight } =
+                action
+            {
+                // The last weighted call gets the remainder of the division.
+                let gas = if weight.0 == 0 {
+                    0
+                } else {
+                    (unused_gas as u128 * weight.0 as u128 / total_weight) as Gas
+                };
+                unused_gas -= gas;
+                total_weight -= weight.0 as u128;
+                let function_call = PromiseAction::FunctionCall {
+                    method_name: core::mem::take(method_name),
+                    arguments: core::mem::take(arguments),
+                    amount: *amount,
+                    gas,
+                };
+                *action = function_call;
+            }
+        }
+    }
+}
+
 impl Drop for Promise {
     fn drop(&mut self) {
+        let mut singles = vec![];
+        self.collect_unscheduled(&mut singles);
+        let has_weighted_function_calls = singles.iter().any(|single| {
+            single
+                .actions
+                .borrow()
+                .iter()
+                .any(|action| matches!(action, PromiseAction::FunctionCallWeight { .. }))
+        });
+        if has_weighted_function_calls {
+            // Panicking again while a unit test unwinds would abort the process.
+            #[cfg(not(target_arch = "wasm32"))]
+            if std::thread::panicking() {
+                return;
+            }
+            distribute_unused_gas(&singles);
+        }
         self.construct_recursively();
     }
 }
 
+#[cfg(feature = "json")]
 impl serde::Serialize for Promise {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -428,8 +629,8 @@ impl borsh::BorshSerialize for Promise {
     }
 }
 
-#[derive(serde::Serialize)]
-#[serde(untagged)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+#[cfg_attr(feature = "json", serde(untagged))]
 pub enum PromiseOrValue<T> {
     Promise(Promise),
     Value(T),
@@ -466,3 +667,138 @@ impl<T: borsh::BorshSerialize> borsh::BorshSerialize for PromiseOrValue<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{get_created_receipts, get_mocked_receipts, VMContextBuilder};
+    use crate::testing_env;
+
+    #[cfg(feature = "json")]
+    fn function_call_gas() -> Vec<Gas> {
+        // Deposits are u128, which can only be serialized to a string.
+        let receipts = serde_json::to_string(&get_created_receipts()).unwrap();
+        serde_json::from_str::<serde_json::Value>(&receipts)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|receipt| receipt["actions"].as_array().unwrap().clone())
+            .filter_map(|action| action["FunctionCall"]["gas"].as_u64())
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_function_call_weight() {
+        let prepaid_gas: Gas = 300_000_000_000_000;
+        testing_env!(VMContextBuilder::new().prepaid_gas(prepaid_gas).build());
+        Promise::new("bob.near".parse().unwrap())
+            .function_call_weight(b"a".to_vec(), vec![], 0, GasWeight(3))
+            .and(Promise::new("carol.near".parse().unwrap()).function_call(
+                b"b".to_vec(),
+                vec![],
+                0,
+                10_000_000_000_000,
+            ))
+            .then(Promise::new("alice.near".parse().unwrap()).function_call_weight(
+                b"c".to_vec(),
+                vec![],
+                0,
+                GasWeight(1),
+            ));
+
+        let gas = function_call_gas();
+        assert_eq!(gas.len(), 3);
+        assert_eq!(gas[1], 10_000_000_000_000);
+        assert!((gas[0] as i128 - 3 * gas[2] as i128).abs() <= 3);
+        let reserved = DEFAULT_GAS_RESERVED_FOR_EXECUTION
+            + 3 * GAS_RESERVED_PER_FUNCTION_CALL
+            + 2 * GAS_RESERVED_PER_DEPENDENCY;
+        assert!(gas.iter().sum::<Gas>() + reserved <= prepaid_gas);
+        assert!(gas.iter().sum::<Gas>() + reserved > prepaid_gas - 1_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Not enough prepaid gas left to split between weighted function calls"
+    )]
+    fn test_function_call_weight_separate_promises() {
+        testing_env!(VMContextBuilder::new().prepaid_gas(300_000_000_000_000).build());
+        Promise::new("bob.near".parse().unwrap()).function_call_weight(
+            b"a".to_vec(),
+            vec![],
+            0,
+            GasWeight(1),
+        );
+        // The first promise got all the unused gas when it was scheduled.
+        Promise::new("carol.near".parse().unwrap()).function_call_weight(
+            b"b".to_vec(),
+            vec![],
+            0,
+            GasWeight(1),
+        );
+    }
+
+    #[test]
+    fn test_function_call_weight_scheduled_when_dropped() {
+        testing_env!(VMContextBuilder::new().prepaid_gas(300_000_000_000_000).build());
+        Promise::new("bob.near".parse().unwrap()).function_call_weight(
+            b"a".to_vec(),
+            vec![],
+            0,
+            GasWeight(1),
+        );
+        Promise::new("carol.near".parse().unwrap()).transfer(1);
+        let receivers: Vec<String> =
+            get_mocked_receipts().into_iter().map(|r| r.receiver_id.to_string()).collect();
+        assert_eq!(receivers, vec!["bob.near", "carol.near"]);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_function_call_weight_reserved_gas() {
+        let prepaid_gas: Gas = 100_000_000_000_000;
+        testing_env!(VMContextBuilder::new().prepaid_gas(prepaid_gas).build());
+        Promise::set_gas_reserved_for_execution(50_000_000_000_000);
+        Promise::new("bob.near".parse().unwrap()).function_call_weight(
+            b"a".to_vec(),
+            vec![],
+            0,
+            GasWeight::default(),
+        );
+
+        let gas = function_call_gas();
+        assert!(gas[0] <= prepaid_gas - 50_000_000_000_000 - GAS_RESERVED_PER_FUNCTION_CALL);
+        assert!(gas[0] > prepaid_gas - 51_000_000_000_000 - GAS_RESERVED_PER_FUNCTION_CALL);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Not enough prepaid gas left to split between weighted function calls"
+    )]
+    fn test_function_call_weight_not_enough_gas() {
+        let prepaid_gas = DEFAULT_GAS_RESERVED_FOR_EXECUTION + GAS_RESERVED_PER_FUNCTION_CALL;
+        testing_env!(VMContextBuilder::new().prepaid_gas(prepaid_gas).build());
+        Promise::new("bob.near".parse().unwrap()).function_call_weight(
+            b"a".to_vec(),
+            vec![],
+            0,
+            GasWeight::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Weighted function calls can only be scheduled through")]
+    fn test_function_call_weight_action_added_directly() {
+        testing_env!(VMContextBuilder::new().build());
+        let promise_index = crate::env::promise_batch_create(&"bob.near".parse().unwrap());
+        PromiseAction::FunctionCallWeight {
+            method_name: b"a".to_vec(),
+            arguments: vec![],
+            amount: 0,
+            weight: GasWeight::default(),
+        }
+        .add(promise_index);
+    }
+}
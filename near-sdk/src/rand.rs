@@ -0,0 +1,215 @@
+//! Deterministic random number generation built on top of [`env::random_seed`].
+//!
+//! The random seed is the same for all receipts executed within a block, so [`Rng::new`] mixes it
+//! with the block index and a domain separator provided by the contract. Use different domains
+//! for independent random values, e.g. `b"lottery-winner"` and `b"bonus-round"`.
+//!
+//! # Limitations
+//!
+//! The seed is produced by the block producer, who can see it before the block is published and
+//! can choose not to produce a block. It should not be used when a validator would profit from
+//! influencing the outcome by more than the block reward, and all values produced within the same
+//! block and domain are identical between calls and transactions.
+//!
+//! In unit tests the seed is taken from the testing context, so the results can be reproduced by
+//! setting [`VMContextBuilder::random_seed`].
+//!
+//! ```
+//! use near_sdk::rand::Rng;
+//! use near_sdk::test_utils::VMContextBuilder;
+//! use near_sdk::testing_env;
+//!
+//! testing_env!(VMContextBuilder::new().random_seed(vec![1; 32]).build());
+//! let mut rng = Rng::new(b"dice");
+//! let roll = rng.gen_range(1..7);
+//! assert!((1..7).contains(&roll));
+//! assert_eq!(Rng::new(b"dice").gen_range(1..7), roll);
+//! ```
+//!
+//! [`env::random_seed`]: crate::env::random_seed
+//! [`VMContextBuilder::random_seed`]: crate::test_utils::VMContextBuilder::random_seed
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::env;
+
+/// Random number generator that derives values from SHA-256 of the seed and a counter.
+pub struct Rng {
+    seed: Vec<u8>,
+    counter: u64,
+    buffer: Vec<u8>,
+}
+
+impl Rng {
+    /// Creates a generator seeded with [`env::random_seed`], [`env::block_index`] and `domain`.
+    ///
+    /// [`env::random_seed`]: crate::env::random_seed
+    /// [`env::block_index`]: crate::env::block_index
+    pub fn new(domain: &[u8]) -> Self {
+        let mut seed = env::random_seed();
+        seed.extend_from_slice(&env::block_index().to_le_bytes());
+        seed.extend_from_slice(domain);
+        Self::from_seed(&seed)
+    }
+
+    /// Creates a generator from an arbitrary seed.
+    pub fn from_seed(seed: &[u8]) -> Self {
+        Self { seed: env::sha256(seed), counter: 0, buffer: Vec::new() }
+    }
+
+    /// Fills `dest` with random bytes.
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            if self.buffer.is_empty() {
+                self.refill();
+            }
+            *byte = self.buffer.pop().unwrap();
+        }
+    }
+
+    /// Returns a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Returns a random `u32`.
+    pub fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    /// Returns a value uniformly distributed in `range`. Panics if the range is empty.
+    pub fn gen_range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "Cannot sample from an empty range");
+        let span = range.end - range.start;
+        // Reject values from the incomplete last span to avoid modulo bias.
+        let zone = u64::MAX - (u64::MAX - span + 1) % span;
+        loop {
+            let value = self.next_u64();
+            if value <= zone {
+                return range.start + value % span;
+            }
+        }
+    }
+
+    /// Shuffles the slice in place using the Fisher-Yates algorithm.
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.gen_range(0..i as u64 + 1) as usize;
+            slice.swap(i, j);
+        }
+    }
+
+    /// Picks an element with probability proportional to its weight. Returns `None` if the slice
+    /// is empty or all weights are zero.
+    pub fn choose_weighted<'a, T, F>(&mut self, items: &'a [T], weight: F) -> Option<&'a T>
+    where
+        F: Fn(&T) -> u64,
+    {
+        let total: u128 = items.iter().map(|item| weight(item) as u128).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut target = if total <= u64::MAX as u128 {
+            self.gen_range(0..total as u64) as u128
+        } else {
+            // Slightly biased, but only reachable when the weights add up to more than `u64::MAX`.
+            ((self.next_u64() as u128) << 64 | self.next_u64() as u128) % total
+        };
+        for item in items {
+            let w = weight(item) as u128;
+            if target < w {
+                return Some(item);
+            }
+            target -= w;
+        }
+        unreachable!()
+    }
+
+    fn refill(&mut self) {
+        let mut data = self.seed.clone();
+        data.extend_from_slice(&self.counter.to_le_bytes());
+        self.counter += 1;
+        self.buffer = env::sha256(&data);
+        debug_assert_eq!(self.buffer.len(), 32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::VMContextBuilder;
+    use crate::testing_env;
+
+    #[test]
+    fn test_reproducible() {
+        testing_env!(VMContextBuilder::new().random_seed(vec![7; 32]).build());
+        let values: Vec<u64> = {
+            let mut rng = Rng::new(b"a");
+            (0..10).map(|_| rng.next_u64()).collect()
+        };
+        let mut rng = Rng::new(b"a");
+        assert_eq!(values, (0..10).map(|_| rng.next_u64()).collect::<Vec<_>>());
+
+        let mut other = Rng::new(b"b");
+        assert_ne!(values[0], other.next_u64());
+
+        testing_env!(VMContextBuilder::new().random_seed(vec![7; 32]).block_index(1).build());
+        assert_ne!(values[0], Rng::new(b"a").next_u64());
+    }
+
+    #[test]
+    fn test_gen_range() {
+        testing_env!(VMContextBuilder::new().random_seed(vec![1; 32]).build());
+        let mut rng = Rng::new(b"range");
+        let mut seen = [false; 6];
+        for _ in 0..200 {
+            let value = rng.gen_range(10..16);
+            assert!((10..16).contains(&value));
+            seen[(value - 10) as usize] = true;
+        }
+        assert!(seen.iter().all(|x| *x));
+        assert_eq!(rng.gen_range(5..6), 5);
+        assert!(rng.gen_range(0..u64::MAX) < u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot sample from an empty range")]
+    fn test_gen_range_empty() {
+        Rng::from_seed(b"seed").gen_range(3..3);
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut rng = Rng::from_seed(b"shuffle");
+        let mut values: Vec<u32> = (0..50).collect();
+        rng.shuffle(&mut values);
+        assert_ne!(values, (0..50).collect::<Vec<_>>());
+        values.sort();
+        assert_eq!(values, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_choose_weighted() {
+        let mut rng = Rng::from_seed(b"weighted");
+        let items = [("never", 0u64), ("rare", 1), ("often", 99)];
+        let mut rare = 0;
+        for _ in 0..1000 {
+            match rng.choose_weighted(&items, |item| item.1).unwrap().0 {
+                "never" => panic!("Item with zero weight was chosen"),
+                "rare" => rare += 1,
+                _ => {}
+            }
+        }
+        assert!(rare < 100);
+
+        assert!(rng.choose_weighted(&items[..1], |item| item.1).is_none());
+        assert!(rng.choose_weighted(&[] as &[(u8, u64)], |item| item.1).is_none());
+        let huge = [u64::MAX, u64::MAX];
+        assert!(rng.choose_weighted(&huge, |w| *w).is_some());
+    }
+}
@@ -6,6 +6,7 @@
 
 mod impls;
 
+use alloc::vec::Vec;
 use borsh::{BorshDeserialize, BorshSerialize};
 use once_cell::unsync::OnceCell;
 
@@ -1,5 +1,6 @@
 mod impls;
 
+use alloc::boxed::Box;
 use borsh::{BorshDeserialize, BorshSerialize};
 use once_cell::unsync::OnceCell;
 
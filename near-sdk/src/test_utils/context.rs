@@ -4,6 +4,10 @@ use crate::AccountId;
 use crate::{
     Balance, BlockHeight, EpochHeight, Gas, PromiseResult, PublicKey, StorageUsage, VMContext,
 };
+use std::boxed::Box;
+use std::string::ToString;
+use std::vec;
+use std::vec::Vec;
 
 /// Returns a pre-defined account_id from a list of 6.
 pub fn accounts(id: usize) -> AccountId {
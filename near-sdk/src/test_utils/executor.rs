@@ -0,0 +1,336 @@
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::string::{String, ToString};
+use std::vec;
+use std::vec::Vec;
+
+use crate::environment::mocked_blockchain::MockedBlockchain;
+use crate::test_utils::{MockedAction, MockedFunctionCall, MockedReceipt, VMContextBuilder};
+use crate::{env, AccountId, PromiseResult, ReturnData, VMContext};
+
+type Storage = HashMap<Vec<u8>, Vec<u8>>;
+type Handler = Box<dyn FnMut(&MockedFunctionCall)>;
+
+/// Receipt executed by the [`ReceiptExecutor`].
+#[derive(Debug)]
+pub struct ExecutedReceipt {
+    /// Position of the receipt in the order of creation, which is what `receipt_indices` of the
+    /// receipt refer to. Receipts created by the initial call come first.
+    pub id: u64,
+    pub predecessor_id: AccountId,
+    pub receipt: MockedReceipt,
+    /// Result as seen by the callbacks of this receipt. If the receipt returned a promise, this is
+    /// the result of that promise.
+    pub result: PromiseResult,
+    pub logs: Vec<String>,
+}
+
+/// Executes the receipts created in a unit test against contracts registered by account ID,
+/// without compiling them to wasm.
+///
+/// Each function call is executed in a fresh [`MockedBlockchain`] with the receiver as the current
+/// account, the arguments as input and the results of the receipts it depends on as promise
+/// results. Every account has its own storage. The result of a call is what the handler passed to
+/// [`env::value_return`] or [`env::promise_return`], and a panic in the handler, including
+/// [`env::panic`], fails the receipt and reverts the storage of the receiver. State kept outside
+/// of the storage, e.g. in the contract instance captured by the handler, is not reverted.
+///
+/// Only function calls can be executed. Panics on receipts with other actions, e.g. transfers,
+/// since balances and accounts are not tracked.
+///
+/// ```
+/// use near_sdk::test_utils::{accounts, ReceiptExecutor, VMContextBuilder};
+/// use near_sdk::{env, testing_env, Promise, PromiseResult};
+///
+/// const GAS: u64 = 5_000_000_000_000;
+///
+/// testing_env!(VMContextBuilder::new().current_account_id(accounts(0)).build());
+/// Promise::new(accounts(1))
+///     .function_call(b"ping".to_vec(), vec![], 0, GAS)
+///     .then(Promise::new(accounts(0)).function_call(b"on_ping".to_vec(), vec![], 0, GAS));
+///
+/// let mut executor = ReceiptExecutor::new();
+/// executor.register(accounts(1), |_call| env::value_return(b"pong"));
+/// executor.register(accounts(0), |_call| match env::promise_result(0) {
+///     PromiseResult::Successful(value) => env::log(&value),
+///     _ => env::panic(b"ping failed"),
+/// });
+/// let executed = executor.execute_created_receipts();
+/// assert_eq!(executed[1].logs, vec!["pong".to_string()]);
+/// ```
+///
+/// [`MockedBlockchain`]: crate::MockedBlockchain
+/// [`env::value_return`]: crate::env::value_return
+/// [`env::promise_return`]: crate::env::promise_return
+/// [`env::panic`]: crate::env::panic
+#[derive(Default)]
+pub struct ReceiptExecutor {
+    contracts: HashMap<AccountId, Handler>,
+    storages: HashMap<AccountId, Storage>,
+}
+
+enum Outcome {
+    Result(PromiseResult),
+    /// The receipt returned the promise of the receipt with the given ID.
+    Forward(u64),
+}
+
+struct PendingReceipt {
+    id: u64,
+    predecessor_id: AccountId,
+    receipt: MockedReceipt,
+}
+
+impl ReceiptExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles function calls to `account_id`, usually by deserializing the arguments and calling
+    /// the method of a contract instance.
+    pub fn register<F: FnMut(&MockedFunctionCall) + 'static>(
+        &mut self,
+        account_id: AccountId,
+        handler: F,
+    ) {
+        self.contracts.insert(account_id, Box::new(handler));
+    }
+
+    /// Executes the receipts created so far in the current testing environment and all receipts
+    /// created by them, in order of their dependencies. Afterwards the testing environment is
+    /// restored, with any changes made to the storage of the current account by the callbacks.
+    ///
+    /// Panics if a function call is made to an account that is not registered, or if a receipt has
+    /// actions other than function calls.
+    pub fn execute_created_receipts(&mut self) -> Vec<ExecutedReceipt> {
+        let context = current_context();
+        let account_id = env::current_account_id();
+        let mut blockchain =
+            env::take_blockchain_interface().expect("Blockchain interface is not set");
+        let mocked =
+            blockchain.as_mut_mocked_blockchain().expect("MockedBlockchain interface expected");
+        let mut pending: Vec<PendingReceipt> = mocked
+            .mocked_receipts()
+            .expect("Receipts are not recorded with a custom memory")
+            .into_iter()
+            .enumerate()
+            .map(|(id, receipt)| PendingReceipt {
+                id: id as u64,
+                predecessor_id: account_id.clone(),
+                receipt,
+            })
+            .collect();
+        self.storages.insert(account_id.clone(), mocked.take_storage());
+
+        let mut next_id = pending.len() as u64;
+        let mut outcomes = HashMap::new();
+        let mut executed = vec![];
+        while !pending.is_empty() {
+            let position = pending
+                .iter()
+                .position(|p| {
+                    p.receipt.receipt_indices.iter().all(|i| resolve(&outcomes, *i).is_some())
+                })
+                .expect("Receipts have circular dependencies");
+            let PendingReceipt { id, predecessor_id, receipt } = pending.remove(position);
+            let promise_results =
+                receipt.receipt_indices.iter().map(|i| resolve(&outcomes, *i).unwrap()).collect();
+            let (outcome, created, logs) =
+                self.execute(&context, &predecessor_id, &receipt, promise_results);
+
+            let offset = next_id;
+            for mut created_receipt in created {
+                created_receipt.receipt_indices.iter_mut().for_each(|i| *i += offset);
+                pending.push(PendingReceipt {
+                    id: next_id,
+                    predecessor_id: receipt.receiver_id.clone(),
+                    receipt: created_receipt,
+                });
+                next_id += 1;
+            }
+            let outcome = match outcome {
+                Outcome::Forward(index) => Outcome::Forward(offset + index),
+                outcome => outcome,
+            };
+            outcomes.insert(id, outcome);
+            executed.push((id, predecessor_id, receipt, logs));
+        }
+
+        let storage = self.storages.remove(&account_id).unwrap_or_default();
+        mocked.restore_storage(storage);
+        env::set_blockchain_interface(blockchain);
+        executed
+            .into_iter()
+            .map(|(id, predecessor_id, receipt, logs)| ExecutedReceipt {
+                id,
+                predecessor_id,
+                receipt,
+                result: resolve(&outcomes, id).expect("Returned promise was not executed"),
+                logs,
+            })
+            .collect()
+    }
+
+    fn execute(
+        &mut self,
+        context: &VMContext,
+        predecessor_id: &AccountId,
+        receipt: &MockedReceipt,
+        promise_results: Vec<PromiseResult>,
+    ) -> (Outcome, Vec<MockedReceipt>, Vec<String>) {
+        let initial_storage = self.storages.remove(&receipt.receiver_id).unwrap_or_default();
+        let mut storage = initial_storage.clone();
+        let mut outcome = Outcome::Result(PromiseResult::Successful(vec![]));
+        let mut created: Vec<MockedReceipt> = vec![];
+        let mut logs = vec![];
+        for action in receipt.actions.iter() {
+            let call = match action {
+                MockedAction::FunctionCall(call) => call,
+                action => panic!(
+                    "Unsupported action in a receipt to {}, only function calls can be executed: {:?}",
+                    receipt.receiver_id, action
+                ),
+            };
+            let handler = self.contracts.get_mut(&receipt.receiver_id).unwrap_or_else(|| {
+                panic!("No contract registered for account {}", receipt.receiver_id)
+            });
+            let mut context = context.clone();
+            context.current_account_id = receipt.receiver_id.to_string();
+            context.predecessor_account_id = predecessor_id.to_string();
+            context.input = call.args.clone();
+            context.attached_deposit = call.deposit;
+            context.prepaid_gas = call.gas;
+            env::set_blockchain_interface(Box::new(MockedBlockchain::new(
+                context,
+                Default::default(),
+                Default::default(),
+                promise_results.clone(),
+                storage,
+                Default::default(),
+                None,
+            )));
+
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                handler(call);
+            }));
+
+            let mut blockchain = env::take_blockchain_interface().unwrap();
+            let mocked = blockchain.as_mut_mocked_blockchain().unwrap();
+            logs.extend(mocked.logs());
+            if result.is_err() {
+                self.storages.insert(receipt.receiver_id.clone(), initial_storage);
+                return (Outcome::Result(PromiseResult::Failed), vec![], logs);
+            }
+            // Receipts created by previous function calls of the same batch come first.
+            let base = created.len() as u64;
+            for mut created_receipt in mocked.mocked_receipts().unwrap() {
+                created_receipt.receipt_indices.iter_mut().for_each(|i| *i += base);
+                created.push(created_receipt);
+            }
+            outcome = match mocked.outcome().return_data {
+                ReturnData::Value(value) => Outcome::Result(PromiseResult::Successful(value)),
+                ReturnData::ReceiptIndex(index) => Outcome::Forward(base + index),
+                ReturnData::None => Outcome::Result(PromiseResult::Successful(vec![])),
+            };
+            storage = mocked.take_storage();
+        }
+        self.storages.insert(receipt.receiver_id.clone(), storage);
+        (outcome, created, logs)
+    }
+}
+
+/// Result of the receipt, or `None` if it or the promise it returned has not been executed yet.
+fn resolve(outcomes: &HashMap<u64, Outcome>, id: u64) -> Option<PromiseResult> {
+    match outcomes.get(&id)? {
+        Outcome::Result(result) => Some(result.clone()),
+        Outcome::Forward(id) => resolve(outcomes, *id),
+    }
+}
+
+/// Context of the current testing environment, used as the base for executed receipts.
+fn current_context() -> VMContext {
+    let mut builder = VMContextBuilder::new();
+    builder
+        .signer_account_id(env::signer_account_id())
+        .signer_account_pk(env::signer_account_pk())
+        .block_index(env::block_index())
+        .block_timestamp(env::block_timestamp())
+        .epoch_height(env::epoch_height())
+        .random_seed(env::random_seed());
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::accounts;
+    use crate::{testing_env, Promise};
+
+    const GAS: u64 = 5_000_000_000_000;
+
+    #[test]
+    fn test_execute_chain() {
+        testing_env!(VMContextBuilder::new().current_account_id(accounts(0)).build());
+        // `get` makes a nested call, so it needs gas for the fees of that call too.
+        Promise::new(accounts(1))
+            .function_call(b"get".to_vec(), vec![], 0, 4 * GAS)
+            .and(Promise::new(accounts(2)).function_call(b"fail".to_vec(), vec![], 0, GAS))
+            .then(Promise::new(accounts(0)).function_call(b"on_get".to_vec(), vec![], 0, GAS));
+
+        let mut executor = ReceiptExecutor::new();
+        executor.register(accounts(1), |call| {
+            assert_eq!(call.method_name, "get");
+            assert_eq!(env::predecessor_account_id(), accounts(0));
+            env::storage_write(b"k", b"v");
+            // Forward the result of a nested call.
+            Promise::new(accounts(2)).function_call(b"nested".to_vec(), vec![], 0, GAS).as_return();
+        });
+        executor.register(accounts(2), |call| match call.method_name.as_str() {
+            "nested" => env::value_return(b"42"),
+            _ => {
+                env::storage_write(b"k", b"v");
+                env::panic(b"Failed")
+            }
+        });
+        executor.register(accounts(0), |_| {
+            assert_eq!(env::promise_results_count(), 2);
+            assert_eq!(env::promise_result(0), PromiseResult::Successful(b"42".to_vec()));
+            assert_eq!(env::promise_result(1), PromiseResult::Failed);
+            env::storage_write(b"callback", b"done");
+            env::log_str("done");
+        });
+
+        let executed = executor.execute_created_receipts();
+        let order: Vec<_> = executed.iter().map(|r| r.id).collect();
+        assert_eq!(order, vec![0, 1, 3, 2]);
+        assert_eq!(executed[0].result, PromiseResult::Successful(b"42".to_vec()));
+        assert_eq!(executed[1].result, PromiseResult::Failed);
+        assert_eq!(executed[2].predecessor_id, accounts(1));
+        assert_eq!(executed[3].logs, vec!["done".to_string()]);
+        assert_eq!(executor.storages[&accounts(1)].get(&b"k"[..]), Some(&b"v".to_vec()));
+        assert!(executor.storages[&accounts(2)].is_empty());
+
+        // The testing environment is restored with the changes made by the callback.
+        assert_eq!(env::current_account_id(), accounts(0));
+        assert_eq!(env::storage_read(b"callback"), Some(b"done".to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "No contract registered for account bob")]
+    fn test_unregistered_account() {
+        testing_env!(VMContextBuilder::new().build());
+        Promise::new(accounts(1)).function_call(b"get".to_vec(), vec![], 0, GAS);
+        ReceiptExecutor::new().execute_created_receipts();
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported action in a receipt to bob")]
+    fn test_unsupported_action() {
+        testing_env!(VMContextBuilder::new().build());
+        Promise::new(accounts(1)).transfer(1).function_call(b"get".to_vec(), vec![], 0, GAS);
+        let mut executor = ReceiptExecutor::new();
+        executor.register(accounts(1), |_call| {});
+        executor.execute_created_receipts();
+    }
+}
@@ -1,10 +1,19 @@
 use crate::env;
+use std::string::String;
+use std::vec::Vec;
 
 #[allow(dead_code)]
 pub mod test_env;
 
 mod context;
 pub use context::{accounts, testing_env_with_promise_results, VMContextBuilder};
+
+mod executor;
+pub use executor::{ExecutedReceipt, ReceiptExecutor};
+
+pub(crate) mod receipts;
+pub use receipts::{FunctionCallMatcher, MockedAction, MockedFunctionCall, MockedReceipt};
+
 use near_vm_logic::mocks::mock_external::Receipt;
 
 /// Initializes a testing environment to mock interactions which would otherwise go through a
@@ -66,18 +75,20 @@ use near_vm_logic::mocks::mock_external::Receipt;
 #[macro_export]
 macro_rules! testing_env {
     ($context:expr, $config:expr, $fee_config:expr, $validators:expr, $promise_results:expr $(,)?) => {
-        $crate::env::set_blockchain_interface(Box::new($crate::MockedBlockchain::new(
-            $context,
-            $config,
-            $fee_config,
-            $promise_results,
-            match $crate::env::take_blockchain_interface() {
-                Some(mut bi) => bi.as_mut_mocked_blockchain().unwrap().take_storage(),
-                None => Default::default(),
-            },
-            $validators,
-            None,
-        )));
+        $crate::env::set_blockchain_interface($crate::alloc::boxed::Box::new(
+            $crate::MockedBlockchain::new(
+                $context,
+                $config,
+                $fee_config,
+                $promise_results,
+                match $crate::env::take_blockchain_interface() {
+                    Some(mut bi) => bi.as_mut_mocked_blockchain().unwrap().take_storage(),
+                    None => Default::default(),
+                },
+                $validators,
+                None,
+            ),
+        ));
     };
     ($context:expr, $config:expr, $fee_config:expr, $validators:expr $(,)?) => {
         $crate::testing_env!($context, $config, $fee_config, $validators, Default::default());
@@ -118,6 +129,25 @@ pub fn get_created_receipts() -> Vec<Receipt> {
     receipts
 }
 
+/// Accessing receipts created by the contract, with the dependencies between them and the raw
+/// arguments of function calls. Only available in unit tests.
+///
+/// See [`assert_promise!`] for checking the actions of a receipt.
+///
+/// [`assert_promise!`]: crate::assert_promise
+#[allow(dead_code)]
+pub fn get_mocked_receipts() -> Vec<MockedReceipt> {
+    let blockchain_interface =
+        env::take_blockchain_interface().expect("Blockchain interface is not set");
+    let receipts = blockchain_interface
+        .as_mocked_blockchain()
+        .expect("MockedBlockchain interface expected")
+        .mocked_receipts()
+        .expect("Receipts are not recorded with a custom memory");
+    env::set_blockchain_interface(blockchain_interface);
+    receipts
+}
+
 /// Objects stored on the trie directly should have identifiers. If identifier is not provided
 /// explicitly than `Default` trait would use this index to generate an id.
 #[allow(dead_code)]
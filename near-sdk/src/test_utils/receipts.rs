@@ -0,0 +1,447 @@
+use crate::{AccountId, Balance, Gas, PublicKey};
+use std::format;
+use std::string::{String, ToString};
+use std::vec;
+use std::vec::Vec;
+
+/// Receipt created by the contract during a unit test, recorded by the [`MockedBlockchain`].
+///
+/// [`MockedBlockchain`]: crate::MockedBlockchain
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockedReceipt {
+    pub receiver_id: AccountId,
+    /// Indices of the receipts that have to complete before this one is executed. A receipt
+    /// scheduled with `a.then(b)` depends on `a`, with `a.and(b).then(c)` on both `a` and `b`.
+    pub receipt_indices: Vec<u64>,
+    pub actions: Vec<MockedAction>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockedAction {
+    CreateAccount,
+    DeployContract {
+        code: Vec<u8>,
+    },
+    FunctionCall(MockedFunctionCall),
+    Transfer {
+        deposit: Balance,
+    },
+    Stake {
+        stake: Balance,
+        public_key: PublicKey,
+    },
+    AddKeyWithFullAccess {
+        public_key: PublicKey,
+        nonce: u64,
+    },
+    AddKeyWithFunctionCall {
+        public_key: PublicKey,
+        nonce: u64,
+        allowance: Option<Balance>,
+        receiver_id: AccountId,
+        method_names: Vec<String>,
+    },
+    DeleteKey {
+        public_key: PublicKey,
+    },
+    DeleteAccount {
+        beneficiary_id: AccountId,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockedFunctionCall {
+    pub method_name: String,
+    pub args: Vec<u8>,
+    pub gas: Gas,
+    pub deposit: Balance,
+}
+
+impl MockedReceipt {
+    /// Function calls of this receipt, in the order they were added.
+    pub fn function_calls(&self) -> impl Iterator<Item = &MockedFunctionCall> {
+        self.actions.iter().filter_map(|action| match action {
+            MockedAction::FunctionCall(call) => Some(call),
+            _ => None,
+        })
+    }
+
+    /// First function call of this receipt to the given method.
+    pub fn function_call(&self, method_name: &str) -> Option<&MockedFunctionCall> {
+        self.function_calls().find(|call| call.method_name == method_name)
+    }
+}
+
+impl MockedFunctionCall {
+    /// Deserializes the arguments from JSON.
+    #[cfg(feature = "json")]
+    pub fn args_json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.args)
+    }
+
+    /// Deserializes the arguments from Borsh.
+    pub fn args_borsh<T: borsh::BorshDeserialize>(&self) -> std::io::Result<T> {
+        T::try_from_slice(&self.args)
+    }
+}
+
+enum ExpectedArgs {
+    #[cfg(feature = "json")]
+    Json(serde_json::Value),
+    Raw(Vec<u8>),
+}
+
+/// Checks a function call of a [`MockedReceipt`]. Usually constructed through [`assert_promise!`].
+///
+/// [`assert_promise!`]: crate::assert_promise
+pub struct FunctionCallMatcher {
+    method_name: String,
+    args: Option<ExpectedArgs>,
+    receiver_id: Option<String>,
+    deposit: Option<Balance>,
+    gas: Option<Gas>,
+}
+
+impl FunctionCallMatcher {
+    pub fn new(method_name: &str) -> Self {
+        Self {
+            method_name: method_name.to_string(),
+            args: None,
+            receiver_id: None,
+            deposit: None,
+            gas: None,
+        }
+    }
+
+    /// Expects the arguments to be equal to the given value when both are parsed as JSON, so the
+    /// order of the fields does not matter.
+    #[cfg(feature = "json")]
+    pub fn args_json<T: serde::Serialize>(mut self, args: &T) -> Self {
+        self.args = Some(ExpectedArgs::Json(
+            serde_json::to_value(args).expect("Failed to serialize the expected arguments"),
+        ));
+        self
+    }
+
+    /// Expects the arguments to be the Borsh serialization of the given value.
+    pub fn args_borsh<T: borsh::BorshSerialize>(mut self, args: &T) -> Self {
+        self.args = Some(ExpectedArgs::Raw(
+            args.try_to_vec().expect("Failed to serialize the expected arguments"),
+        ));
+        self
+    }
+
+    pub fn receiver_id<T: ToString>(mut self, receiver_id: T) -> Self {
+        self.receiver_id = Some(receiver_id.to_string());
+        self
+    }
+
+    pub fn deposit(mut self, deposit: Balance) -> Self {
+        self.deposit = Some(deposit);
+        self
+    }
+
+    pub fn gas(mut self, gas: Gas) -> Self {
+        self.gas = Some(gas);
+        self
+    }
+
+    /// Panics with a description of the receipt if it doesn't contain a matching function call.
+    ///
+    /// Any function call to the method may match, so a receipt can call the same method several
+    /// times with different arguments. On failure, all calls to the method are listed with the
+    /// reason they don't match.
+    pub fn assert_matches(&self, receipt: &MockedReceipt) {
+        if let Some(receiver_id) = &self.receiver_id {
+            assert_eq!(receipt.receiver_id.as_str(), receiver_id, "Unexpected receiver");
+        }
+        let mut mismatches = vec![];
+        for call in receipt.function_calls().filter(|call| call.method_name == self.method_name) {
+            match self.mismatch(call) {
+                Some(mismatch) => mismatches.push(mismatch),
+                None => return,
+            }
+        }
+        if mismatches.is_empty() {
+            panic!("No function call to `{}` in {:?}", self.method_name, receipt);
+        }
+        panic!(
+            "No matching function call to `{}` in {:?}:\n{}",
+            self.method_name,
+            receipt,
+            mismatches.join("\n")
+        );
+    }
+
+    /// Describes why the given call to the method doesn't match, or returns `None` if it does.
+    fn mismatch(&self, call: &MockedFunctionCall) -> Option<String> {
+        match &self.args {
+            #[cfg(feature = "json")]
+            Some(ExpectedArgs::Json(expected)) => match call.args_json::<serde_json::Value>() {
+                Ok(args) if &args == expected => {}
+                Ok(args) => {
+                    return Some(format!(
+                        "Unexpected arguments of `{}`: {} instead of {}",
+                        self.method_name, args, expected
+                    ))
+                }
+                Err(_) => {
+                    return Some(format!(
+                        "Arguments of `{}` are not JSON: {:?}",
+                        self.method_name,
+                        String::from_utf8_lossy(&call.args)
+                    ))
+                }
+            },
+            Some(ExpectedArgs::Raw(expected)) if &call.args != expected => {
+                return Some(format!(
+                    "Unexpected arguments of `{}`: {:?} instead of {:?}",
+                    self.method_name, call.args, expected
+                ));
+            }
+            _ => {}
+        }
+        if let Some(deposit) = self.deposit.filter(|deposit| *deposit != call.deposit) {
+            return Some(format!(
+                "Unexpected deposit of `{}`: {} instead of {}",
+                self.method_name, call.deposit, deposit
+            ));
+        }
+        if let Some(gas) = self.gas.filter(|gas| *gas != call.gas) {
+            return Some(format!(
+                "Unexpected gas of `{}`: {} instead of {}",
+                self.method_name, call.gas, gas
+            ));
+        }
+        None
+    }
+}
+
+/// Asserts that a [`MockedReceipt`] contains the given action.
+///
+/// Function calls are matched by method name and arguments, and optionally by `deposit`, `gas`
+/// and `receiver_id`. Use `function_call` for JSON arguments and `function_call_borsh` for Borsh
+/// arguments. Transfers are matched by the amount.
+///
+/// # Example use
+///
+/// ```
+/// use near_sdk::serde_json::json;
+/// use near_sdk::test_utils::{get_mocked_receipts, VMContextBuilder};
+/// use near_sdk::{assert_promise, testing_env, Promise};
+///
+/// testing_env!(VMContextBuilder::new().build());
+/// Promise::new("token.near".parse().unwrap()).function_call(
+///     b"ft_on_transfer".to_vec(),
+///     br#"{"amount":"10","msg":""}"#.to_vec(),
+///     1,
+///     10_000_000_000_000,
+/// );
+///
+/// let receipts = get_mocked_receipts();
+/// assert_promise!(
+///     receipts[0],
+///     function_call("ft_on_transfer", json!({ "msg": "", "amount": "10" }), deposit = 1)
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_promise {
+    ($receipt:expr, function_call($method:expr, $args:expr $(, $field:ident = $value:expr)* $(,)?)) => {
+        $crate::test_utils::FunctionCallMatcher::new($method)
+            .args_json(&$args)
+            $(.$field($value))*
+            .assert_matches(&$receipt)
+    };
+    ($receipt:expr, function_call_borsh($method:expr, $args:expr $(, $field:ident = $value:expr)* $(,)?)) => {
+        $crate::test_utils::FunctionCallMatcher::new($method)
+            .args_borsh(&$args)
+            $(.$field($value))*
+            .assert_matches(&$receipt)
+    };
+    ($receipt:expr, transfer($amount:expr)) => {{
+        let receipt: &$crate::test_utils::MockedReceipt = &$receipt;
+        let amount: $crate::Balance = $amount;
+        assert!(
+            receipt.actions.contains(&$crate::test_utils::MockedAction::Transfer { deposit: amount }),
+            "No transfer of {} in {:?}",
+            amount,
+            receipt
+        );
+    }};
+}
+
+/// Mirrors the promises created through the `MockedBlockchain` to keep the raw arguments, which
+/// are lost in the receipts recorded by `near-vm-logic`.
+#[derive(Default)]
+pub(crate) struct ReceiptRecorder {
+    receipts: Vec<MockedReceipt>,
+    /// Receipt indices each promise index resolves to. Joint promises resolve to several receipts.
+    promises: Vec<Vec<u64>>,
+}
+
+impl ReceiptRecorder {
+    pub(crate) fn receipts(&self) -> &[MockedReceipt] {
+        &self.receipts
+    }
+
+    /// Creates a new receipt, optionally depending on the receipts of the promise `after`.
+    pub(crate) fn create(&mut self, receiver_id: AccountId, after: Option<u64>) {
+        let receipt_indices =
+            after.map(|promise_index| self.promises[promise_index as usize].clone());
+        self.promises.push(vec![self.receipts.len() as u64]);
+        self.receipts.push(MockedReceipt {
+            receiver_id,
+            receipt_indices: receipt_indices.unwrap_or_default(),
+            actions: vec![],
+        });
+    }
+
+    pub(crate) fn and(&mut self, promise_indices: &[u64]) {
+        let receipt_indices =
+            promise_indices.iter().flat_map(|&i| self.promises[i as usize].clone()).collect();
+        self.promises.push(receipt_indices);
+    }
+
+    pub(crate) fn add_action(&mut self, promise_index: u64, action: MockedAction) {
+        // `VMLogic` has already checked that the promise is not a joint promise.
+        let receipt_index = self.promises[promise_index as usize][0];
+        self.receipts[receipt_index as usize].actions.push(action);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{get_mocked_receipts, VMContextBuilder};
+    use crate::{testing_env, Promise};
+
+    #[test]
+    fn test_promise_tree() {
+        testing_env!(VMContextBuilder::new().build());
+        let args = vec![0xff, 0x00, 0x80];
+        Promise::new("bob.near".parse().unwrap())
+            .function_call(b"a".to_vec(), args.clone(), 1, 1_000)
+            .and(Promise::new("carol.near".parse().unwrap()).transfer(10))
+            .then(Promise::new("alice.near".parse().unwrap()).function_call(
+                b"b".to_vec(),
+                vec![],
+                0,
+                2_000,
+            ));
+
+        let receipts = get_mocked_receipts();
+        assert_eq!(receipts.len(), 3);
+        assert_eq!(receipts[0].receiver_id.as_str(), "bob.near");
+        assert_eq!(
+            receipts[0].actions,
+            vec![MockedAction::FunctionCall(MockedFunctionCall {
+                method_name: "a".to_string(),
+                args,
+                gas: 1_000,
+                deposit: 1,
+            })]
+        );
+        assert!(receipts[0].receipt_indices.is_empty());
+        assert_eq!(receipts[1].actions, vec![MockedAction::Transfer { deposit: 10 }]);
+        assert_eq!(receipts[2].receipt_indices, vec![0, 1]);
+        assert_eq!(receipts[2].function_call("b").unwrap().gas, 2_000);
+    }
+
+    #[test]
+    fn test_assert_promise_borsh() {
+        testing_env!(VMContextBuilder::new().build());
+        let args = (u128::MAX, "msg".to_string());
+        Promise::new("bob.near".parse().unwrap()).transfer(5).function_call(
+            b"on_transfer".to_vec(),
+            borsh::BorshSerialize::try_to_vec(&args).unwrap(),
+            0,
+            10,
+        );
+
+        let receipts = get_mocked_receipts();
+        assert_eq!(
+            receipts[0]
+                .function_call("on_transfer")
+                .unwrap()
+                .args_borsh::<(u128, String)>()
+                .unwrap(),
+            args
+        );
+        assert_promise!(
+            receipts[0],
+            function_call_borsh("on_transfer", args, gas = 10, receiver_id = "bob.near")
+        );
+        assert_promise!(receipts[0], transfer(5));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_assert_promise_json() {
+        testing_env!(VMContextBuilder::new().build());
+        Promise::new("bob.near".parse().unwrap()).function_call(
+            b"ft_on_transfer".to_vec(),
+            br#"{"sender_id":"alice","amount":"100","msg":""}"#.to_vec(),
+            0,
+            10,
+        );
+
+        let receipts = get_mocked_receipts();
+        let expected = serde_json::json!({ "amount": "100", "msg": "", "sender_id": "alice" });
+        assert_promise!(receipts[0], function_call("ft_on_transfer", expected, deposit = 0));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    #[should_panic(expected = "Unexpected deposit of `ft_on_transfer`")]
+    fn test_assert_promise_wrong_deposit() {
+        testing_env!(VMContextBuilder::new().build());
+        Promise::new("bob.near".parse().unwrap()).function_call(
+            b"ft_on_transfer".to_vec(),
+            b"{}".to_vec(),
+            0,
+            10,
+        );
+
+        let receipts = get_mocked_receipts();
+        assert_promise!(
+            receipts[0],
+            function_call("ft_on_transfer", serde_json::json!({}), deposit = 1)
+        );
+    }
+
+    #[test]
+    fn test_assert_promise_same_method_twice() {
+        testing_env!(VMContextBuilder::new().build());
+        Promise::new("bob.near".parse().unwrap())
+            .function_call(
+                b"ping".to_vec(),
+                borsh::BorshSerialize::try_to_vec(&1u8).unwrap(),
+                0,
+                10,
+            )
+            .function_call(
+                b"ping".to_vec(),
+                borsh::BorshSerialize::try_to_vec(&2u8).unwrap(),
+                0,
+                20,
+            );
+
+        let receipts = get_mocked_receipts();
+        assert_promise!(receipts[0], function_call_borsh("ping", 1u8, gas = 10));
+        assert_promise!(receipts[0], function_call_borsh("ping", 2u8, gas = 20));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Unexpected gas of `ping`: 10 instead of 30\nUnexpected gas of `ping`: 20 instead of 30"
+    )]
+    fn test_assert_promise_lists_all_candidates() {
+        testing_env!(VMContextBuilder::new().build());
+        Promise::new("bob.near".parse().unwrap())
+            .function_call(b"ping".to_vec(), vec![], 0, 10)
+            .function_call(b"ping".to_vec(), vec![], 0, 20);
+
+        let receipts = get_mocked_receipts();
+        FunctionCallMatcher::new("ping").gas(30).assert_matches(&receipts[0]);
+    }
+}
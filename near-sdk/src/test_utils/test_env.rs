@@ -1,5 +1,8 @@
 use crate::test_utils::VMContextBuilder;
 use crate::{env, AccountId, MockedBlockchain, VMConfig};
+use std::boxed::Box;
+use std::string::ToString;
+use std::vec;
 
 pub fn alice() -> AccountId {
     AccountId::new_unchecked("alice.near".to_string())
@@ -0,0 +1,251 @@
+use alloc::vec::Vec;
+use borsh::maybestd::collections::HashMap;
+use borsh::maybestd::io::{Error, Write};
+use borsh::BorshSchema;
+use core::marker::PhantomData;
+
+use crate::{Promise, PromiseOrValue};
+
+/// A [`Promise`] that carries the type `T` of its eventual result, so that callbacks can be
+/// checked at compile time.
+///
+/// Typed promises are created by the functions in the `typed_calls` submodule generated by
+/// `#[ext_contract]`. Methods without callback arguments return `TypedPromise<R>`, where `R` is the
+/// return type of the method, while methods with `#[callback]`, `#[callback_result]` or
+/// `#[callback_vec]` arguments return a [`TypedCallback`] that can only be attached to a promise
+/// of the matching type:
+///
+/// ```
+/// use near_sdk::{env, ext_contract, near_bindgen, AccountId, TypedPromise};
+/// use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+///
+/// #[ext_contract(ext_token)]
+/// pub trait Token {
+///     fn balance_of(&self, account_id: AccountId) -> u64;
+/// }
+///
+/// #[ext_contract(ext_self)]
+/// pub trait Callbacks {
+///     fn on_balance(&mut self, #[callback] balance: u64) -> bool;
+///     fn on_balances(&mut self, #[callback_vec] balances: Vec<u64>);
+/// }
+///
+/// #[near_bindgen]
+/// #[derive(Default, BorshDeserialize, BorshSerialize)]
+/// pub struct Contract {}
+///
+/// const GAS: u64 = 5_000_000_000_000;
+///
+/// #[near_bindgen]
+/// impl Contract {
+///     pub fn check(&self, account_id: AccountId) -> TypedPromise<bool> {
+///         ext_token::typed_calls::balance_of(account_id, &"token.near", 0, GAS)
+///             .then_map(ext_self::typed_calls::on_balance(&env::current_account_id(), 0, GAS))
+///     }
+///
+///     pub fn check_all(&self, accounts: Vec<AccountId>) -> TypedPromise<()> {
+///         let balances = accounts
+///             .into_iter()
+///             .map(|id| ext_token::typed_calls::balance_of(id, &"token.near", 0, GAS))
+///             .collect();
+///         TypedPromise::join_all(balances)
+///             .then_map(ext_self::typed_calls::on_balances(&env::current_account_id(), 0, GAS))
+///     }
+/// }
+/// ```
+///
+/// Attaching a callback that expects a different type, e.g. `#[callback] balance: String`, fails
+/// to compile.
+pub struct TypedPromise<T> {
+    promise: Promise,
+    _marker: PhantomData<T>,
+}
+
+/// The result type of [`TypedPromise::join_all`]: the results of all joined promises, collected
+/// into `V`, e.g. `Joined<Vec<u64>>`. Only callbacks with a `#[callback_vec]` argument of type `V`
+/// can be attached, since a `#[callback]` argument would only read the first result.
+pub struct Joined<V>(PhantomData<V>);
+
+/// A function call that reads the result of type `T` of the promise it is attached to, and
+/// returns `R`. Attached with [`TypedPromise::then_map`].
+pub struct TypedCallback<T, R> {
+    promise: Promise,
+    _marker: PhantomData<(T, R)>,
+}
+
+impl<T> TypedPromise<T> {
+    /// Asserts that the result of the given promise has type `T`.
+    pub fn new(promise: Promise) -> Self {
+        Self { promise, _marker: PhantomData }
+    }
+
+    /// Schedules the callback right after this promise, passing the result of this promise to it.
+    pub fn then_map<R>(self, callback: TypedCallback<T, R>) -> TypedPromise<R> {
+        TypedPromise::new(self.promise.then(callback.promise))
+    }
+
+    /// Schedules an untyped promise right after this promise. See [`Promise::then`].
+    pub fn then(self, other: Promise) -> Promise {
+        self.promise.then(other)
+    }
+
+    /// Executes this promise concurrently with `other`. The callback receives both results, e.g.
+    /// through two `#[callback]` arguments.
+    ///
+    /// Chaining `and` nests the result types, e.g. `a.and(b).and(c)` has the type `((A, B), C)`.
+    /// Use [`TypedPromise::all`] to join more than two promises into a flat tuple.
+    pub fn and<U>(self, other: TypedPromise<U>) -> TypedPromise<(T, U)> {
+        TypedPromise::new(self.promise.and(other.promise))
+    }
+
+    /// Executes a tuple of up to six promises concurrently. The callback receives all results in
+    /// the same order, e.g. `TypedPromise::all((a, b, c))` with one `#[callback]` argument for
+    /// each of `a`, `b` and `c`.
+    pub fn all<P: TypedPromiseTuple<Output = T>>(promises: P) -> Self {
+        TypedPromise::new(promises.join())
+    }
+
+    /// Executes all promises concurrently. The callback receives all results through a
+    /// `#[callback_vec]` argument. Panics if `promises` is empty.
+    pub fn join_all(promises: Vec<TypedPromise<T>>) -> TypedPromise<Joined<Vec<T>>> {
+        let mut promises = promises.into_iter().map(|p| p.promise);
+        let first = promises.next().expect("Cannot join an empty list of promises");
+        TypedPromise::new(promises.fold(first, |acc, p| acc.and(p)))
+    }
+
+    /// See [`Promise::as_return`].
+    pub fn as_return(self) -> Self {
+        Self::new(self.promise.as_return())
+    }
+
+    /// Drops the type information.
+    pub fn into_promise(self) -> Promise {
+        self.promise
+    }
+}
+
+/// Tuple of typed promises that can be joined with [`TypedPromise::all`].
+pub trait TypedPromiseTuple {
+    /// Tuple of the result types of the promises.
+    type Output;
+
+    /// Joins the promises with [`Promise::and`].
+    fn join(self) -> Promise;
+}
+
+macro_rules! impl_typed_promise_tuple {
+    ($first_ty:ident $first:ident, $($ty:ident $promise:ident),+) => {
+        impl<$first_ty, $($ty),+> TypedPromiseTuple
+            for (TypedPromise<$first_ty>, $(TypedPromise<$ty>),+)
+        {
+            type Output = ($first_ty, $($ty),+);
+
+            fn join(self) -> Promise {
+                let ($first, $($promise),+) = self;
+                $first.promise$(.and($promise.promise))+
+            }
+        }
+    };
+}
+
+impl_typed_promise_tuple!(A a, B b);
+impl_typed_promise_tuple!(A a, B b, C c);
+impl_typed_promise_tuple!(A a, B b, C c, D d);
+impl_typed_promise_tuple!(A a, B b, C c, D d, E e);
+impl_typed_promise_tuple!(A a, B b, C c, D d, E e, F f);
+
+impl<T, R> TypedCallback<T, R> {
+    /// Asserts that the given promise is a function call which reads a result of type `T` and
+    /// returns `R`.
+    pub fn new(promise: Promise) -> Self {
+        Self { promise, _marker: PhantomData }
+    }
+
+    /// Drops the type information.
+    pub fn into_promise(self) -> Promise {
+        self.promise
+    }
+}
+
+impl<T> From<TypedPromise<T>> for Promise {
+    fn from(promise: TypedPromise<T>) -> Self {
+        promise.promise
+    }
+}
+
+impl<T> From<TypedPromise<T>> for PromiseOrValue<T> {
+    fn from(promise: TypedPromise<T>) -> Self {
+        PromiseOrValue::Promise(promise.promise)
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> serde::Serialize for TypedPromise<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(&self.promise, serializer)
+    }
+}
+
+impl<T> borsh::BorshSerialize for TypedPromise<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        borsh::BorshSerialize::serialize(&self.promise, writer)
+    }
+}
+
+/// Serialized as the result type, since the return value of the promise becomes the return value
+/// of the contract call.
+impl<T: BorshSchema> BorshSchema for TypedPromise<T> {
+    fn add_definitions_recursively(
+        definitions: &mut HashMap<borsh::schema::Declaration, borsh::schema::Definition>,
+    ) {
+        T::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> borsh::schema::Declaration {
+        T::declaration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{get_mocked_receipts, VMContextBuilder};
+    use crate::testing_env;
+
+    const GAS: u64 = 5_000_000_000_000;
+
+    fn call<T>(account_id: &str, method_name: &str) -> TypedPromise<T> {
+        TypedPromise::new(Promise::new(account_id.parse().unwrap()).function_call(
+            method_name.as_bytes().to_vec(),
+            vec![],
+            0,
+            GAS,
+        ))
+    }
+
+    #[test]
+    fn test_all() {
+        testing_env!(VMContextBuilder::new().build());
+        let joined: TypedPromise<(u32, String, bool)> = TypedPromise::all((
+            call::<u32>("a.near", "get_u32"),
+            call::<String>("b.near", "get_string"),
+            call::<bool>("c.near", "get_bool"),
+        ));
+        let callback: TypedCallback<(u32, String, bool), ()> =
+            TypedCallback::new(Promise::new("alice.near".parse().unwrap()).function_call(
+                b"on_all".to_vec(),
+                vec![],
+                0,
+                GAS,
+            ));
+        joined.then_map(callback);
+
+        let receipts = get_mocked_receipts();
+        assert_eq!(receipts.len(), 4);
+        assert_eq!(receipts[3].receiver_id.to_string(), "alice.near");
+        assert_eq!(receipts[3].receipt_indices, vec![0, 1, 2]);
+    }
+}
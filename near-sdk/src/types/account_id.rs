@@ -1,8 +1,14 @@
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::{String, ToString};
+use borsh::maybestd::io::{self, ErrorKind};
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use core::borrow::Borrow;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::Deref;
+#[cfg(feature = "json")]
 use serde::{de, Deserialize, Serialize};
-use std::convert::TryFrom;
-use std::fmt;
-use std::io::{self, ErrorKind};
 
 use crate::env::is_valid_account_id;
 
@@ -34,9 +40,8 @@ use crate::env::is_valid_account_id;
 /// ```
 ///
 /// [`FromStr`]: std::str::FromStr
-#[derive(
-    Debug, Clone, PartialEq, PartialOrd, Ord, Eq, BorshSerialize, Serialize, Hash, BorshSchema,
-)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq, BorshSerialize, Hash, BorshSchema)]
+#[cfg_attr(feature = "json", derive(Serialize))]
 pub struct AccountId(String);
 
 impl AccountId {
@@ -58,6 +63,143 @@ impl AccountId {
     }
 }
 
+impl Deref for AccountId {
+    type Target = AccountIdRef;
+
+    fn deref(&self) -> &AccountIdRef {
+        AccountIdRef::new_unchecked(self.0.as_str())
+    }
+}
+
+impl Borrow<AccountIdRef> for AccountId {
+    fn borrow(&self) -> &AccountIdRef {
+        self
+    }
+}
+
+impl PartialEq<AccountIdRef> for AccountId {
+    fn eq(&self, other: &AccountIdRef) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<'a> PartialEq<&'a AccountIdRef> for AccountId {
+    fn eq(&self, other: &&'a AccountIdRef) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<AccountId> for AccountIdRef {
+    fn eq(&self, other: &AccountId) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// Borrowed version of [`AccountId`], which allows to check and compare account IDs without
+/// allocating. [`AccountId`] dereferences to this type, so all of its methods are also available on
+/// owned account IDs.
+///
+/// # Examples
+/// ```
+/// use near_sdk::{AccountId, AccountIdRef};
+///
+/// let factory = AccountIdRef::new("factory.near").unwrap();
+/// let token: AccountId = factory.get_sub_account("token").unwrap();
+/// assert_eq!(token.as_str(), "token.factory.near");
+/// assert!(token.is_sub_account_of(factory));
+/// assert_eq!(token.parent(), Some(factory));
+/// assert!(!token.is_top_level());
+/// ```
+#[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Hash)]
+#[repr(transparent)]
+pub struct AccountIdRef(str);
+
+impl AccountIdRef {
+    /// Validates the string and returns it as an account ID reference.
+    pub fn new<S: AsRef<str> + ?Sized>(id: &S) -> Result<&Self, ParseAccountIdError> {
+        let id = id.as_ref();
+        validate_account_id(id)?;
+        Ok(Self::new_unchecked(id))
+    }
+
+    /// Constructs new `AccountIdRef` from a string without checking validity.
+    /// Creating an invalid account id will result in a runtime error when being used.
+    pub fn new_unchecked<S: AsRef<str> + ?Sized>(id: &S) -> &Self {
+        let id = id.as_ref();
+        debug_assert!(is_valid_account_id(id.as_bytes()));
+        // Safety: `AccountIdRef` is a `repr(transparent)` wrapper around `str`.
+        unsafe { &*(id as *const str as *const Self) }
+    }
+
+    /// Returns reference to the account ID bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Returns reference to the account ID string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `true` if the account ID doesn't have a parent account, e.g. `near` or an implicit
+    /// account.
+    pub fn is_top_level(&self) -> bool {
+        !self.0.contains('.')
+    }
+
+    /// Returns `true` if the account ID is an implicit account, which is 64 lowercase hex
+    /// characters of the ED25519 public key.
+    pub fn is_implicit(&self) -> bool {
+        self.0.len() == 64 && self.0.bytes().all(|b| matches!(b, b'a'..=b'f' | b'0'..=b'9'))
+    }
+
+    /// Returns `true` if the account ID is a direct sub-account of `parent`, e.g. `app.alice.near`
+    /// is a sub-account of `alice.near`, but not of `near`.
+    pub fn is_sub_account_of(&self, parent: &AccountIdRef) -> bool {
+        self.parent() == Some(parent)
+    }
+
+    /// Returns the account which can create this account, or `None` for top-level accounts.
+    pub fn parent(&self) -> Option<&AccountIdRef> {
+        self.0.find('.').map(|idx| Self::new_unchecked(&self.0[idx + 1..]))
+    }
+
+    /// Builds the account ID of the sub-account `name.<self>`. Fails if `name` contains a `.` or
+    /// the resulting account ID is invalid, e.g. too long.
+    pub fn get_sub_account(&self, name: &str) -> Result<AccountId, ParseAccountIdError> {
+        if name.contains('.') {
+            return Err(ParseAccountIdError {});
+        }
+        AccountId::try_from(format!("{}.{}", name, &self.0))
+    }
+}
+
+impl fmt::Display for AccountIdRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl AsRef<str> for AccountIdRef {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl ToOwned for AccountIdRef {
+    type Owned = AccountId;
+
+    fn to_owned(&self) -> AccountId {
+        AccountId(self.0.to_string())
+    }
+}
+
+impl<'a> From<&'a AccountIdRef> for AccountId {
+    fn from(id: &'a AccountIdRef) -> Self {
+        id.to_owned()
+    }
+}
+
 impl fmt::Display for AccountId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Display::fmt(&self.0, f)
@@ -76,6 +218,7 @@ impl AsRef<str> for AccountId {
     }
 }
 
+#[cfg(feature = "json")]
 impl<'de> Deserialize<'de> for AccountId {
     fn deserialize<D>(deserializer: D) -> Result<Self, <D as de::Deserializer<'de>>::Error>
     where
@@ -88,8 +231,9 @@ impl<'de> Deserialize<'de> for AccountId {
 
 impl BorshDeserialize for AccountId {
     fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
-        <String as BorshDeserialize>::deserialize(buf)
-            .and_then(|s| Self::try_from(s).map_err(|e| io::Error::new(ErrorKind::InvalidData, e)))
+        <String as BorshDeserialize>::deserialize(buf).and_then(|s| {
+            Self::try_from(s).map_err(|e| io::Error::new(ErrorKind::InvalidData, e.to_string()))
+        })
     }
 }
 
@@ -110,7 +254,7 @@ impl TryFrom<String> for AccountId {
     }
 }
 
-impl std::str::FromStr for AccountId {
+impl core::str::FromStr for AccountId {
     type Err = ParseAccountIdError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
@@ -129,6 +273,7 @@ impl fmt::Display for ParseAccountIdError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for ParseAccountIdError {}
 
 #[cfg(test)]
@@ -136,6 +281,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg(feature = "json")]
     fn test_deser() {
         let key: AccountId = serde_json::from_str("\"alice.near\"").unwrap();
         assert_eq!(key.0, "alice.near".to_string());
@@ -145,6 +291,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "json")]
     fn test_ser() {
         let key: AccountId = "alice.near".parse().unwrap();
         let actual: String = serde_json::to_string(&key).unwrap();
@@ -165,4 +312,47 @@ mod tests {
         // Test to make sure the account ID is serialized as a string through borsh
         assert_eq!(str::try_to_vec(&id).unwrap(), account_id.try_to_vec().unwrap());
     }
+
+    #[test]
+    fn test_sub_accounts() {
+        let alice = AccountIdRef::new("alice.near").unwrap();
+        let app: AccountId = "app.alice.near".parse().unwrap();
+        assert!(app.is_sub_account_of(alice));
+        assert!(!app.is_sub_account_of(AccountIdRef::new("near").unwrap()));
+        assert!(!alice.is_sub_account_of(alice));
+        assert_eq!(app.parent(), Some(alice));
+        assert_eq!(alice.parent().unwrap().as_str(), "near");
+        assert_eq!(alice.parent().unwrap().parent(), None);
+
+        assert_eq!(alice.get_sub_account("app"), Ok(app));
+        assert!(alice.get_sub_account("a.b").is_err());
+        assert!(alice.get_sub_account("").is_err());
+        assert!(alice.get_sub_account(&"a".repeat(60)).is_err());
+    }
+
+    #[test]
+    fn test_account_kinds() {
+        assert!(AccountIdRef::new("near").unwrap().is_top_level());
+        assert!(!AccountIdRef::new("alice.near").unwrap().is_top_level());
+
+        let implicit = "a".repeat(32) + &"0".repeat(32);
+        assert!(AccountIdRef::new(&implicit).unwrap().is_implicit());
+        assert!(AccountIdRef::new(&implicit).unwrap().is_top_level());
+        assert!(!AccountIdRef::new(&"g".repeat(64)).unwrap().is_implicit());
+        assert!(!AccountIdRef::new("alice.near").unwrap().is_implicit());
+    }
+
+    #[test]
+    fn test_account_id_ref_cmp() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let alice_ref = AccountIdRef::new("alice.near").unwrap();
+        assert_eq!(alice, alice_ref);
+        assert_eq!(*alice_ref, alice);
+        assert_eq!(alice_ref.to_owned(), alice);
+        assert!(AccountIdRef::new("Alice.near").is_err());
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(alice.clone());
+        assert!(set.contains(alice_ref));
+    }
 }
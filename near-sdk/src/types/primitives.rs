@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 #[cfg(not(target_arch = "wasm32"))]
 use near_primitives_core::hash::CryptoHash;
 
@@ -30,6 +31,19 @@ pub type Balance = u128;
 /// Gas is a type for storing amount of gas.
 pub type Gas = u64;
 
+/// Share of the unused prepaid gas attached to a function call, relative to the weights of the
+/// other function calls scheduled together with it. See [`Promise::function_call_weight`].
+///
+/// [`Promise::function_call_weight`]: crate::Promise::function_call_weight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GasWeight(pub u64);
+
+impl Default for GasWeight {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
 /// Number of blocks in current group.
 pub type NumBlocks = u64;
 /// Number of shards in current group.
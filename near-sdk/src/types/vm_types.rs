@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 #[cfg(not(target_arch = "wasm32"))]
 pub use near_vm_logic::types::{PromiseResult as VmPromiseResult, ReturnData};
 
@@ -10,7 +11,7 @@ pub type IteratorIndex = u64;
 
 /// When there is a callback attached to one or more contract calls the execution results of these
 /// calls are available to the contract invoked through the callback.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PromiseResult {
     /// Current version of the protocol never returns `PromiseResult::NotReady`.
     NotReady,
@@ -18,6 +19,29 @@ pub enum PromiseResult {
     Failed,
 }
 
+impl PromiseResult {
+    /// Converts the result into the raw returned bytes if the promise was successful.
+    pub fn into_result(self) -> Result<Vec<u8>, PromiseError> {
+        match self {
+            PromiseResult::NotReady => Err(PromiseError::NotReady),
+            PromiseResult::Successful(data) => Ok(data),
+            PromiseResult::Failed => Err(PromiseError::Failed),
+        }
+    }
+}
+
+/// Error of a promise result that can be handled by the callback, e.g. through
+/// `#[callback_result] x: Result<T, PromiseError>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromiseError {
+    /// Current version of the protocol never returns `PromiseResult::NotReady`.
+    NotReady,
+    /// The promise failed, any state changes it made were reverted.
+    Failed,
+    /// The promise succeeded, but its result can't be deserialized into the expected type.
+    Deserialization,
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 impl From<PromiseResult> for VmPromiseResult {
     fn from(p: PromiseResult) -> Self {
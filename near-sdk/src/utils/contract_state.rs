@@ -0,0 +1,88 @@
+/// Describes where the state of a contract is stored. Implemented automatically by
+/// `#[near_bindgen]` on the contract struct, and the generated method wrappers use
+/// [`STATE_KEY`] to load and save the contract with [`env::state_read_key`] and
+/// [`env::state_write_key`]. Contracts whose state type doesn't implement the trait, e.g. a
+/// struct without `#[near_bindgen]` or an enum, are stored under [`env::STATE_KEY`].
+///
+/// The key can be changed with `#[near_bindgen(state_key = "...")]`, which lets two logical
+/// contracts share an account, e.g. during a migration:
+///
+/// ```
+/// use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+/// use near_sdk::{near_bindgen, ContractState};
+///
+/// #[near_bindgen(state_key = "v2")]
+/// #[derive(Default, BorshDeserialize, BorshSerialize)]
+/// pub struct Contract {
+///     value: u64,
+/// }
+///
+/// assert_eq!(<Contract as ContractState>::STATE_KEY, b"v2");
+/// ```
+///
+/// [`STATE_KEY`]: ContractState::STATE_KEY
+/// [`env::state_read_key`]: crate::env::state_read_key
+/// [`env::state_write_key`]: crate::env::state_write_key
+/// [`env::STATE_KEY`]: crate::env::STATE_KEY
+pub trait ContractState {
+    /// Storage key under which the contract struct is serialized.
+    const STATE_KEY: &'static [u8] = crate::env::STATE_KEY;
+}
+
+/// Resolves the state key of a contract type, falling back to `env::STATE_KEY` if the type doesn't
+/// implement `ContractState`. Method resolution prefers `ContractStateKey`, which takes the
+/// reference by value, over `DefaultStateKey`, which needs another auto-reference.
+#[doc(hidden)]
+pub struct StateKeyOf<T>(pub core::marker::PhantomData<T>);
+
+#[doc(hidden)]
+pub trait ContractStateKey {
+    fn state_key(&self) -> &'static [u8];
+}
+
+impl<T: ContractState> ContractStateKey for StateKeyOf<T> {
+    fn state_key(&self) -> &'static [u8] {
+        T::STATE_KEY
+    }
+}
+
+#[doc(hidden)]
+pub trait DefaultStateKey {
+    fn state_key(&self) -> &'static [u8];
+}
+
+impl<T> DefaultStateKey for &StateKeyOf<T> {
+    fn state_key(&self) -> &'static [u8] {
+        crate::env::STATE_KEY
+    }
+}
+
+/// State key of the contract type, used by the method wrappers generated by `#[near_bindgen]`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! contract_state_key {
+    ($ty:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::utils::{ContractStateKey as _, DefaultStateKey as _};
+        (&$crate::utils::StateKeyOf::<$ty>(core::marker::PhantomData)).state_key()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Plain;
+
+    struct Custom;
+
+    impl ContractState for Custom {
+        const STATE_KEY: &'static [u8] = b"custom";
+    }
+
+    #[test]
+    fn state_key_fallback() {
+        assert_eq!(contract_state_key!(Plain), crate::env::STATE_KEY);
+        assert_eq!(contract_state_key!(Custom), b"custom");
+    }
+}
@@ -1,11 +1,18 @@
 pub(crate) mod storage_key_impl;
 
+mod contract_state;
+pub use contract_state::ContractState;
+#[doc(hidden)]
+pub use contract_state::{ContractStateKey, DefaultStateKey, StateKeyOf};
+
 #[cfg(feature = "unstable")]
 mod cache_entry;
 #[cfg(feature = "unstable")]
 pub(crate) use cache_entry::{CacheEntry, EntryState};
 
 use crate::{env, AccountId, PromiseResult};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 /// Helper macro to log a message through [`env::log`].
 /// This macro can be used similar to the [`std::format`] macro in most cases.
@@ -37,7 +44,7 @@ macro_rules! log {
         $crate::env::log_str($arg.as_ref())
     };
     ($($arg:tt)*) => {
-        $crate::env::log_str(format!($($arg)*).as_str())
+        $crate::env::log_str($crate::alloc::format!($($arg)*).as_str())
     };
 }
 
@@ -77,6 +84,7 @@ pub struct PendingContractTx {
 }
 
 impl PendingContractTx {
+    #[cfg(feature = "json")]
     pub fn new(
         receiver_id: AccountId,
         method: &str,
@@ -1,3 +1,5 @@
+use alloc::vec;
+use alloc::vec::Vec;
 use borsh::BorshSerialize;
 
 /// Converts Self into a [`Vec<u8>`] that is used for a storage key through [`into_storage_key`].